@@ -0,0 +1,17 @@
+//! helpers shared by the benchmarks
+
+/// a small, seeded xorshift generator so that the generated inputs are reproducible
+pub struct XorShift(pub u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
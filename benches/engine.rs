@@ -0,0 +1,301 @@
+//! micro benchmarks of the balance, the engine and the csv pipeline
+//!
+//! all data is generated in-process, so the benchmarks run offline and without any input files.
+//! ```
+//! cargo bench --bench engine
+//! cargo bench --bench engine -- execute/
+//! ```
+mod common;
+
+use booking_engine::{
+    Balance, Client, ClientFormat, ClientId, CsvClientItem, CsvTransactionItem, PaymentEngine,
+    Transaction, TransactionId, TransactionType, SCALE,
+};
+use common::XorShift;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use csv::{ReaderBuilder, Trim, WriterBuilder};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// the client every single transaction benchmark is executed for
+const CLIENT: ClientId = 1;
+/// the id of the booking the benchmarked disputes, resolves and chargebacks refer to
+const BOOKING: TransactionId = 1;
+
+fn balance(c: &mut Criterion) {
+    let mut group = c.benchmark_group("balance");
+    let mut balance = Balance::default();
+    balance.credit(1_000 * SCALE).unwrap();
+
+    // every operation is paired with its inverse, so the balance stays the same across iterations
+    group.bench_function("credit_debit", |b| {
+        b.iter(|| {
+            balance.credit(black_box(SCALE)).unwrap();
+            balance.debit(black_box(SCALE)).unwrap();
+        })
+    });
+    group.bench_function("freeze_unfreeze", |b| {
+        b.iter(|| {
+            balance.freeze(black_box(SCALE)).unwrap();
+            balance.unfreeze(black_box(SCALE)).unwrap();
+        })
+    });
+    group.bench_function("hold_release", |b| {
+        b.iter(|| {
+            balance.hold(black_box(SCALE)).unwrap();
+            balance.release(black_box(SCALE)).unwrap();
+        })
+    });
+
+    group.finish();
+}
+
+/// an engine in which `CLIENT` has deposited `BOOKING` and another client holds some funds
+fn engine() -> PaymentEngine {
+    let mut engine = PaymentEngine::default();
+    engine
+        .execute(Transaction::new(
+            BOOKING,
+            CLIENT,
+            TransactionType::Deposit(100 * SCALE),
+        ))
+        .unwrap();
+    engine
+        .execute(Transaction::new(2, 2, TransactionType::Deposit(SCALE)))
+        .unwrap();
+
+    engine
+}
+
+fn transaction(transaction_type: TransactionType) -> Transaction {
+    let tx = match transaction_type {
+        TransactionType::Deposit(_)
+        | TransactionType::Withdrawal(_)
+        | TransactionType::Transfer { .. } => 3,
+        TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+            BOOKING
+        }
+        // admin transactions do not refer to a booking
+        _ => 0,
+    };
+
+    Transaction::new(tx, CLIENT, transaction_type)
+}
+
+/// executes a single transaction of every type on a small engine
+///
+/// each transaction runs on a freshly prepared engine, as most of them can only succeed once.
+fn execute(c: &mut Criterion) {
+    let mut group = c.benchmark_group("execute");
+    type Case = (
+        &'static str,
+        fn() -> TransactionType,
+        &'static [fn() -> TransactionType],
+    );
+    let cases: [Case; 9] = [
+        ("deposit", || TransactionType::Deposit(SCALE), &[]),
+        ("withdrawal", || TransactionType::Withdrawal(SCALE), &[]),
+        (
+            "transfer",
+            || TransactionType::Transfer {
+                to: 2,
+                amount: SCALE,
+            },
+            &[],
+        ),
+        ("dispute", || TransactionType::Dispute, &[]),
+        (
+            "resolve",
+            || TransactionType::Resolve,
+            &[|| TransactionType::Dispute],
+        ),
+        (
+            "chargeback",
+            || TransactionType::Chargeback,
+            &[|| TransactionType::Dispute],
+        ),
+        ("lock", || TransactionType::Lock, &[]),
+        (
+            "unlock",
+            || TransactionType::Unlock,
+            &[|| TransactionType::Lock],
+        ),
+        (
+            "close",
+            || TransactionType::Close,
+            &[|| TransactionType::Withdrawal(100 * SCALE)],
+        ),
+    ];
+
+    for (name, transaction_type, preparation) in cases {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    let mut engine = engine();
+                    for transaction_type in preparation {
+                        engine.execute(transaction(transaction_type())).unwrap();
+                    }
+                    (engine, transaction(transaction_type()))
+                },
+                |(mut engine, transaction)| {
+                    engine.execute(black_box(transaction)).unwrap();
+                    // dropping the engine is not part of the measurement
+                    engine
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+/// an engine with a single client holding the given number of deposits
+fn client_with_bookings(bookings: TransactionId) -> PaymentEngine {
+    let mut engine = PaymentEngine::default();
+    for tx in 1..=bookings {
+        engine
+            .execute(Transaction::new(
+                tx,
+                CLIENT,
+                TransactionType::Deposit(SCALE),
+            ))
+            .unwrap();
+    }
+
+    engine
+}
+
+/// the cost of a transaction of a client with a large booking history
+///
+/// `clone` shows what copying the whole client for every transaction would cost, `dispute_resolve`
+/// what the engine actually spends on staging only the touched balance and booking.
+fn large_clients(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large_client");
+
+    for bookings in [1_000, 10_000, 100_000] {
+        let engine = client_with_bookings(bookings);
+        let client = engine.client(CLIENT).unwrap().unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("clone", bookings),
+            &*client,
+            |b, client: &Client| b.iter(|| client.clone()),
+        );
+
+        // every booking can only be disputed once, so the engine is rebuilt (untimed) once all of
+        // them have been disputed and resolved
+        group.bench_function(BenchmarkId::new("dispute_resolve", bookings), |b| {
+            b.iter_custom(|iterations| {
+                let mut engine = client_with_bookings(bookings);
+                let mut tx = 0;
+                let mut elapsed = Duration::ZERO;
+
+                for _ in 0..iterations {
+                    if tx == bookings {
+                        engine = client_with_bookings(bookings);
+                        tx = 0;
+                    }
+                    tx += 1;
+
+                    let start = Instant::now();
+                    engine
+                        .execute(Transaction::new(tx, CLIENT, TransactionType::Dispute))
+                        .unwrap();
+                    engine
+                        .execute(Transaction::new(tx, CLIENT, TransactionType::Resolve))
+                        .unwrap();
+                    elapsed += start.elapsed();
+                }
+
+                elapsed
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// a csv input of deposits, withdrawals, disputes and resolves with whitespace around the fields
+fn input(rows: u32, clients: u64) -> String {
+    let mut random = XorShift(0x2545_f491_4f6c_dd1d);
+    let mut input = String::from("type, client, tx, amount\n");
+
+    for tx in 1..=rows {
+        let client = random.below(clients);
+        let line = match random.below(10) {
+            0..=5 => format!(
+                "deposit, {}, {}, {}.{:04}",
+                client,
+                tx,
+                random.below(100),
+                tx % 10_000
+            ),
+            6..=7 => format!(
+                "withdrawal, {}, {}, {}.{:04}",
+                client,
+                tx,
+                random.below(10),
+                tx % 10_000
+            ),
+            // disputes and resolves refer to earlier rows, which might belong to another client
+            8 => format!("dispute, {}, {},", client, 1 + random.below(u64::from(tx))),
+            _ => format!("resolve, {}, {},", client, 1 + random.below(u64::from(tx))),
+        };
+        input.push_str(&line);
+        input.push('\n');
+    }
+
+    input
+}
+
+fn parse(input: &str) -> impl Iterator<Item = Transaction> + '_ {
+    ReaderBuilder::new()
+        .trim(Trim::All)
+        .from_reader(input.as_bytes())
+        .into_deserialize::<CsvTransactionItem>()
+        .map(|item| Transaction::try_from(item.unwrap()).unwrap())
+}
+
+fn run(input: &str) -> PaymentEngine {
+    let mut engine = PaymentEngine::default();
+    for transaction in parse(input) {
+        // plenty of the generated disputes and resolves are rejected, just like in real inputs
+        let _ = engine.execute(transaction);
+    }
+
+    engine
+}
+
+fn serialize(engine: &PaymentEngine) -> Vec<u8> {
+    let mut writer = WriterBuilder::new().from_writer(Vec::new());
+    for client in engine.clients().map(Result::unwrap) {
+        for item in CsvClientItem::rows(&client, ClientFormat::default()) {
+            writer.serialize(item).unwrap();
+        }
+    }
+
+    writer.into_inner().unwrap()
+}
+
+/// throughput of reading, executing and writing a csv input end-to-end
+fn csv_pipeline(c: &mut Criterion) {
+    const ROWS: u32 = 100_000;
+    const CLIENTS: u64 = 1_000;
+
+    let mut group = c.benchmark_group("csv");
+    group.sample_size(20);
+    let input = input(ROWS, CLIENTS);
+
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_function("parse", |b| b.iter(|| parse(&input).count()));
+    group.bench_function("parse_execute", |b| b.iter(|| run(&input)));
+
+    let engine = run(&input);
+    group.throughput(Throughput::Elements(engine.clients().count() as u64));
+    group.bench_function("serialize", |b| b.iter(|| serialize(&engine)));
+
+    group.finish();
+}
+
+criterion_group!(benches, balance, execute, large_clients, csv_pipeline);
+criterion_main!(benches);
@@ -0,0 +1,131 @@
+//! end-to-end benchmark of the binary on a large, generated input file
+//!
+//! generates a CSV with `BENCH_ROWS` rows (default `10_000_000`, the size of the README's
+//! `large_input.zip`) spread over `BENCH_CLIENTS` clients (default `100`) and measures how long
+//! the binary takes to process it. As there are only a few clients, every client ends up with a
+//! large booking history, which makes the per-transaction cost of the engine visible.
+//!
+//! ```
+//! cargo bench --bench large_input
+//! BENCH_ROWS=1000000 BENCH_CLIENTS=10 cargo bench --bench large_input
+//! ```
+mod common;
+
+use common::XorShift;
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const ITERATIONS: usize = 3;
+
+fn env_or(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn generate(path: &Path, rows: u64, clients: u64) -> std::io::Result<()> {
+    let mut random = XorShift(0x2545_f491_4f6c_dd1d);
+    let mut writer = BufWriter::new(File::create(path)?);
+    // ids of the deposits/withdrawals booked and currently disputed per client
+    let mut booked: Vec<Vec<u64>> = vec![Vec::new(); clients as usize];
+    let mut disputed: Vec<Vec<u64>> = vec![Vec::new(); clients as usize];
+    let mut tx = 0;
+
+    writeln!(writer, "type, client, tx, amount")?;
+    for _ in 0..rows {
+        let client = random.below(clients);
+        let bookings = &mut booked[client as usize];
+        let disputes = &mut disputed[client as usize];
+
+        match random.below(100) {
+            0..=59 => {
+                tx += 1;
+                bookings.push(tx);
+                let amount = random.below(1_000_000);
+                writeln!(
+                    writer,
+                    "deposit, {}, {}, {}.{:04}",
+                    client,
+                    tx,
+                    amount / 10_000,
+                    amount % 10_000
+                )?;
+            }
+            60..=84 => {
+                tx += 1;
+                bookings.push(tx);
+                let amount = random.below(100_000);
+                writeln!(
+                    writer,
+                    "withdrawal, {}, {}, {}.{:04}",
+                    client,
+                    tx,
+                    amount / 10_000,
+                    amount % 10_000
+                )?;
+            }
+            85..=94 if !bookings.is_empty() => {
+                let reference = bookings[random.below(bookings.len() as u64) as usize];
+                disputes.push(reference);
+                writeln!(writer, "dispute, {}, {},", client, reference)?;
+            }
+            _ => match disputes.pop() {
+                // chargebacks lock the client, so keep them rare
+                Some(reference) if random.below(100_000) == 0 => {
+                    writeln!(writer, "chargeback, {}, {},", client, reference)?
+                }
+                Some(reference) => writeln!(writer, "resolve, {}, {},", client, reference)?,
+                None => writeln!(writer, "resolve, {}, {},", client, tx + 1)?,
+            },
+        }
+    }
+
+    writer.flush()
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`, anything else (e.g. `cargo test --benches`) only checks
+    // that the benchmark builds
+    if !env::args().any(|argument| argument == "--bench") {
+        return;
+    }
+
+    let rows = env_or("BENCH_ROWS", 10_000_000);
+    let clients = env_or("BENCH_CLIENTS", 100);
+    let input = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("bench_{rows}_{clients}.csv"));
+
+    if !input.exists() {
+        println!(
+            "generating {rows} rows for {clients} clients into {}",
+            input.display()
+        );
+        generate(&input, rows, clients).expect("could not generate benchmark input");
+    }
+
+    let mut durations = Vec::with_capacity(ITERATIONS);
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let status = Command::new(env!("CARGO_BIN_EXE_booking_engine"))
+            .arg(&input)
+            .stdout(Stdio::null())
+            .status()
+            .expect("could not run payment engine");
+        durations.push(start.elapsed());
+
+        assert!(status.success(), "payment engine failed: {status}");
+    }
+
+    let fastest = durations.iter().min().copied().unwrap_or_default();
+    let mean = durations.iter().sum::<Duration>() / ITERATIONS as u32;
+    println!(
+        "large_input/{rows}x{clients}: fastest {:.3?}, mean {:.3?}, {:.0} rows/s",
+        fastest,
+        mean,
+        rows as f64 / fastest.as_secs_f64()
+    );
+}
@@ -0,0 +1,23 @@
+//! feeds arbitrary bytes through the csv reader into [CsvTransactionItem]s and [Transaction]s
+//!
+//! rows that can not be read or converted are rejected with an error, none of them may panic.
+//! The converted transactions are executed as well, so that extreme amounts reach the engine.
+#![no_main]
+
+use booking_engine::{CsvTransactionItem, PaymentEngine, Transaction};
+use csv::{ReaderBuilder, Trim};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut engine = PaymentEngine::default();
+    let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(data);
+
+    for item in reader.deserialize::<CsvTransactionItem>() {
+        let Ok(item) = item else {
+            continue;
+        };
+        if let Ok(transaction) = Transaction::try_from(item) {
+            let _ = engine.execute(transaction);
+        }
+    }
+});
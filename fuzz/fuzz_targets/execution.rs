@@ -0,0 +1,70 @@
+//! executes arbitrary sequences of transactions and checks the invariants of the engine (see
+//! `booking_engine::invariants`) after each one
+#![no_main]
+
+use arbitrary::Arbitrary;
+use booking_engine::{
+    invariants, Amount, Asset, ClientId, PaymentEngine, Transaction, TransactionId, TransactionType,
+};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Debug, Arbitrary)]
+enum Kind {
+    Deposit,
+    Withdrawal,
+    Transfer { to: u8 },
+    Dispute,
+    Resolve,
+    Chargeback,
+    Lock,
+    Unlock,
+    Close,
+}
+
+#[derive(Debug, Arbitrary)]
+struct Step {
+    kind: Kind,
+    /// few clients and transaction ids, so that transactions hit existing clients and bookings
+    tx: u8,
+    client: u8,
+    amount: Amount,
+    is_default_asset: bool,
+}
+
+impl Step {
+    fn transaction(&self) -> Transaction {
+        let transaction_type = match self.kind {
+            Kind::Deposit => TransactionType::Deposit(self.amount),
+            Kind::Withdrawal => TransactionType::Withdrawal(self.amount),
+            Kind::Transfer { to } => TransactionType::Transfer {
+                to: ClientId::from(to % 8),
+                amount: self.amount,
+            },
+            Kind::Dispute => TransactionType::Dispute,
+            Kind::Resolve => TransactionType::Resolve,
+            Kind::Chargeback => TransactionType::Chargeback,
+            Kind::Lock => TransactionType::Lock,
+            Kind::Unlock => TransactionType::Unlock,
+            Kind::Close => TransactionType::Close,
+        };
+        let asset = match self.is_default_asset {
+            true => Asset::default(),
+            false => "USD".parse().unwrap(),
+        };
+
+        Transaction::new(
+            TransactionId::from(self.tx),
+            ClientId::from(self.client % 8),
+            transaction_type,
+        )
+        .with_asset(asset)
+    }
+}
+
+fuzz_target!(|steps: Vec<Step>| {
+    let mut engine = PaymentEngine::default();
+
+    for step in steps {
+        let _ = invariants::execute(&mut engine, step.transaction());
+    }
+});
@@ -0,0 +1,130 @@
+use crate::asset::AssetError::{InvalidCharacter, TooLong};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+/// maximum number of characters of an asset code
+pub const MAX_ASSET_LENGTH: usize = 8;
+
+#[derive(Debug, PartialEq)]
+pub enum AssetError {
+    TooLong,
+    InvalidCharacter,
+}
+
+impl Display for AssetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TooLong => write!(f, "asset exceeds {MAX_ASSET_LENGTH} characters"),
+            InvalidCharacter => write!(f, "asset may only contain ascii letters and digits"),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+/// the code of an asset or currency a client holds a balance in (e.g. `USD` or `BTC`)
+///
+/// codes are case-sensitive and stored inline, so that every booking can carry its asset without
+/// an allocation. The empty code is the default asset of rows that do not specify one.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Asset([u8; MAX_ASSET_LENGTH]);
+
+impl Asset {
+    pub fn as_str(&self) -> &str {
+        let length = self
+            .0
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(MAX_ASSET_LENGTH);
+
+        // only ascii characters are ever stored
+        std::str::from_utf8(&self.0[..length]).unwrap_or_default()
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl FromStr for Asset {
+    type Err = AssetError;
+
+    /// parses an asset code, an empty string yields the default asset
+    ///
+    /// # Errors
+    /// - [TooLong] if the code has more than [MAX_ASSET_LENGTH] characters
+    /// - [InvalidCharacter] if the code contains anything but ascii letters and digits
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() > MAX_ASSET_LENGTH {
+            return Err(TooLong);
+        }
+        if !s.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(InvalidCharacter);
+        }
+
+        let mut code = [0; MAX_ASSET_LENGTH];
+        code[..s.len()].copy_from_slice(s.as_bytes());
+
+        Ok(Asset(code))
+    }
+}
+
+impl Display for Asset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Debug for Asset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Asset {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for Asset {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::Asset;
+    use crate::asset::AssetError::{InvalidCharacter, TooLong};
+
+    #[test]
+    fn parses_codes() {
+        assert_eq!("USD".parse::<Asset>().unwrap().as_str(), "USD");
+        assert_eq!("USDT1234".parse::<Asset>().unwrap().as_str(), "USDT1234");
+        assert!("".parse::<Asset>().unwrap().is_default());
+        assert_ne!("usd".parse::<Asset>(), "USD".parse::<Asset>());
+    }
+
+    #[test]
+    fn rejects_invalid_codes() {
+        assert_eq!("USDT12345".parse::<Asset>(), Err(TooLong));
+        assert_eq!("US D".parse::<Asset>(), Err(InvalidCharacter));
+        assert_eq!("€".parse::<Asset>(), Err(InvalidCharacter));
+    }
+
+    #[test]
+    fn orders_by_code() {
+        let mut assets: Vec<Asset> = ["EUR", "", "BTC", "EURC"]
+            .iter()
+            .map(|code| code.parse().unwrap())
+            .collect();
+        assets.sort();
+
+        let codes: Vec<&str> = assets.iter().map(Asset::as_str).collect();
+        assert_eq!(codes, vec!["", "BTC", "EUR", "EURC"]);
+    }
+}
@@ -1,9 +1,12 @@
 use crate::balance::ArithmeticError::{Overflow, Underflow};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 
 pub type Amount = u64;
 type BalanceResult = Result<(), ArithmeticError>;
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Balance {
     frozen: Amount,
     available: Amount,
@@ -15,6 +18,17 @@ pub enum ArithmeticError {
     Underflow,
 }
 
+impl Display for ArithmeticError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Overflow => write!(f, "amount overflow"),
+            Underflow => write!(f, "insufficient funds"),
+        }
+    }
+}
+
+impl Error for ArithmeticError {}
+
 impl Balance {
     pub fn frozen(&self) -> Amount {
         self.frozen
@@ -30,11 +44,13 @@ impl Balance {
     ///
     /// # Examples
     /// ```
+    /// use booking_engine::Balance;
+    ///
     /// let mut account = Balance::default();
-    /// account.deposit(100);
-    /// account.freeze(50);
-    /// assert_eq!(account.frozen, 50);
-    /// assert_eq!(account.available, 50);
+    /// account.credit(100).unwrap();
+    /// account.freeze(50).unwrap();
+    /// assert_eq!(account.frozen(), 50);
+    /// assert_eq!(account.available(), 50);
     /// ```
     /// # Errors
     /// - [Overflow] if `frozen` exceeds the max value
@@ -55,12 +71,14 @@ impl Balance {
     ///
     /// # Examples
     /// ```
+    /// use booking_engine::Balance;
+    ///
     /// let mut account = Balance::default();
-    /// account.deposit(100);
-    /// account.freeze(50);
-    /// account.unfreeze(10);
-    /// assert_eq!(account.frozen, 40);
-    /// assert_eq!(account.available, 60);
+    /// account.credit(100).unwrap();
+    /// account.freeze(50).unwrap();
+    /// account.unfreeze(10).unwrap();
+    /// assert_eq!(account.frozen(), 40);
+    /// assert_eq!(account.available(), 60);
     /// ```
     /// # Errors
     /// - [Overflow] if `available` exceeds the max value
@@ -81,9 +99,11 @@ impl Balance {
     ///
     /// # Examples
     /// ```
+    /// use booking_engine::Balance;
+    ///
     /// let mut account = Balance::default();
-    /// account.deposit(100);
-    /// assert_eq!(account.available, 100);
+    /// account.credit(100).unwrap();
+    /// assert_eq!(account.available(), 100);
     /// ```
     /// # Errors
     /// - [Overflow] if `available` exceeds the max value
@@ -104,10 +124,12 @@ impl Balance {
     ///
     /// # Examples
     /// ```
+    /// use booking_engine::Balance;
+    ///
     /// let mut account = Balance::default();
-    /// account.deposit(100);
-    /// account.withdraw(10);
-    /// assert_eq!(account.available, 90);
+    /// account.credit(100).unwrap();
+    /// account.debit(10).unwrap();
+    /// assert_eq!(account.available(), 90);
     /// ```
     /// # Errors
     /// - [Underflow] if `available` falls below the min value
@@ -117,20 +139,70 @@ impl Balance {
         Ok(())
     }
 
+    /// holds a given amount on behalf of the account
+    ///
+    /// adds a specified amount to `frozen` without touching `available`.
+    ///
+    /// # Examples
+    /// ```
+    /// use booking_engine::Balance;
+    ///
+    /// let mut account = Balance::default();
+    /// account.hold(50).unwrap();
+    /// assert_eq!(account.frozen(), 50);
+    /// assert_eq!(account.available(), 0);
+    /// ```
+    /// # Errors
+    /// - [Overflow] if `frozen` or the total exceeds the max value
+    pub fn hold(&mut self, amount: Amount) -> BalanceResult {
+        let frozen = self.frozen.checked_add(amount).ok_or(Overflow)?;
+
+        // ensure that available + frozen (total) does not overflow
+        frozen.checked_add(self.available).ok_or(Overflow)?;
+
+        self.frozen = frozen;
+
+        Ok(())
+    }
+
+    /// releases a previously held amount
+    ///
+    /// subtracts a specified amount from `frozen` without touching `available`.
+    ///
+    /// # Examples
+    /// ```
+    /// use booking_engine::Balance;
+    ///
+    /// let mut account = Balance::default();
+    /// account.hold(50).unwrap();
+    /// account.release(20).unwrap();
+    /// assert_eq!(account.frozen(), 30);
+    /// assert_eq!(account.available(), 0);
+    /// ```
+    /// # Errors
+    /// - [Underflow] if `frozen` falls below the min value
+    pub fn release(&mut self, amount: Amount) -> BalanceResult {
+        self.frozen = self.frozen.checked_sub(amount).ok_or(Underflow)?;
+
+        Ok(())
+    }
+
     /// removes a given amount from the account balance
     ///
     /// subtracts a specified amount from `frozen`.
     ///
     /// # Examples
     /// ```
+    /// use booking_engine::Balance;
+    ///
     /// let mut account = Balance::default();
-    /// account.deposit(100);
-    /// account.freeze(20);
-    /// assert_eq!(account.available, 80);
-    /// assert_eq!(account.frozen, 20);
-    /// account.chargeback(20);
-    /// assert_eq!(account.available, 80);
-    /// assert_eq!(account.frozen, 0);
+    /// account.credit(100).unwrap();
+    /// account.freeze(20).unwrap();
+    /// assert_eq!(account.available(), 80);
+    /// assert_eq!(account.frozen(), 20);
+    /// account.chargeback(20).unwrap();
+    /// assert_eq!(account.available(), 80);
+    /// assert_eq!(account.frozen(), 0);
     /// ```
     /// # Errors
     /// - [Underflow] if `frozen` falls below the min value
@@ -271,4 +343,35 @@ mod tests {
         assert!(balance.freeze(FREEZE_AMOUNT).is_ok());
         assert_eq!(balance.chargeback(DEPOSIT_AMOUNT).unwrap_err(), Underflow);
     }
+
+    #[test]
+    fn hold_works() {
+        const DEPOSIT_AMOUNT: Amount = 500;
+        const HOLD_AMOUNT: Amount = 100;
+
+        let mut balance = Balance::default();
+        assert!(balance.credit(DEPOSIT_AMOUNT).is_ok());
+        assert!(balance.hold(HOLD_AMOUNT).is_ok());
+        assert_eq!(balance.frozen, HOLD_AMOUNT);
+        assert_eq!(balance.available, DEPOSIT_AMOUNT);
+    }
+
+    #[test]
+    fn hold_cannot_overflow_total_balance() {
+        let mut balance = Balance::default();
+        assert!(balance.credit(Amount::MAX).is_ok());
+        assert_eq!(balance.hold(1).unwrap_err(), Overflow);
+        assert_eq!(balance.frozen, 0);
+    }
+
+    #[test]
+    fn release_cannot_move_more_than_frozen() {
+        const HOLD_AMOUNT: Amount = 100;
+
+        let mut balance = Balance::default();
+        assert!(balance.hold(HOLD_AMOUNT).is_ok());
+        assert_eq!(balance.release(HOLD_AMOUNT + 1).unwrap_err(), Underflow);
+        assert!(balance.release(HOLD_AMOUNT).is_ok());
+        assert_eq!(balance.frozen, 0);
+    }
 }
@@ -0,0 +1,382 @@
+//! generates reproducible csv workloads for the payment engine
+//!
+//! the same seed and options always produce the same rows. Optionally, the output the engine is
+//! expected to produce is computed by a reference model and written alongside.
+//! ```
+//! cargo run --release --bin generate -- --rows 10000000 --clients 100 -o large_input.csv
+//! cargo run --bin generate -- --rows 1000 --expected expected.csv -o input.csv
+//! ```
+use crate::model::Model;
+use booking_engine::{Amount, ClientId, TransactionId, SCALE};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+mod model;
+
+/// number of the most recent bookings a dispute picks from
+const RECENT_BOOKINGS: usize = 100_000;
+/// largest deposit in minor units
+const MAX_DEPOSIT: Amount = 1_000 * SCALE;
+/// largest withdrawal in minor units
+const MAX_WITHDRAWAL: Amount = 500 * SCALE;
+
+/// parses a share between 0 and 1
+fn share(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(share) if (0.0..=1.0).contains(&share) => Ok(share),
+        _ => Err("expected a share between 0 and 1".to_string()),
+    }
+}
+
+/// generates a reproducible csv workload for the payment engine
+#[derive(Debug, Parser)]
+#[command(name = "generate", version, about)]
+struct Args {
+    /// seed of the random generator
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// number of distinct clients
+    #[arg(long, value_name = "N", default_value_t = 1_000, value_parser = clap::value_parser!(u16).range(1..))]
+    clients: u16,
+
+    /// number of rows without the header
+    #[arg(long, value_name = "N", default_value_t = 1_000_000, value_parser = clap::value_parser!(u64).range(..=u64::from(TransactionId::MAX)))]
+    rows: u64,
+
+    /// relative weight of deposits
+    #[arg(long, value_name = "WEIGHT", default_value_t = 70)]
+    deposits: u32,
+
+    /// relative weight of withdrawals
+    #[arg(long, value_name = "WEIGHT", default_value_t = 25)]
+    withdrawals: u32,
+
+    /// relative weight of disputes and the resolves and chargebacks settling them
+    #[arg(long, value_name = "WEIGHT", default_value_t = 5)]
+    disputes: u32,
+
+    /// share of settled disputes that are charged back, which locks the client
+    #[arg(long, value_name = "SHARE", default_value_t = 0.02, value_parser = share)]
+    chargebacks: f64,
+
+    /// share of rows that can not be parsed
+    #[arg(long, value_name = "SHARE", default_value_t = 0.01, value_parser = share)]
+    invalid: f64,
+
+    /// share of rows for clients that have been locked by a chargeback
+    #[arg(long, value_name = "SHARE", default_value_t = 0.01, value_parser = share)]
+    locked: f64,
+
+    /// share of rows with whitespace around their fields
+    #[arg(long, value_name = "SHARE", default_value_t = 0.1, value_parser = share)]
+    whitespace: f64,
+
+    /// writes the rows to the given file instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// writes the output the engine is expected to produce, computed by a reference model
+    #[arg(long, value_name = "PATH")]
+    expected: Option<PathBuf>,
+}
+
+impl Args {
+    /// the sum of all weights, which can not overflow as a u64
+    fn total_weight(&self) -> u64 {
+        u64::from(self.deposits) + u64::from(self.withdrawals) + u64::from(self.disputes)
+    }
+}
+
+/// a seeded splitmix64 generator, so that a seed always produces the same workload
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    fn index(&mut self, length: usize) -> usize {
+        self.below(length as u64) as usize
+    }
+
+    /// true with the given probability
+    fn chance(&mut self, share: f64) -> bool {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < share
+    }
+}
+
+/// generates one row after another and applies each of them to the reference model
+struct Generator<'a> {
+    args: &'a Args,
+    random: Random,
+    model: Model,
+    /// the last id handed out to a deposit or withdrawal
+    tx: TransactionId,
+    /// the most recent deposits and withdrawals, which can be disputed
+    recent: VecDeque<(ClientId, TransactionId)>,
+    /// disputes that have not been settled yet
+    disputed: Vec<(ClientId, TransactionId)>,
+    /// clients locked by a chargeback
+    locked: Vec<ClientId>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(args: &'a Args) -> Self {
+        Self {
+            args,
+            random: Random(args.seed),
+            model: Model::default(),
+            tx: 0,
+            recent: VecDeque::new(),
+            disputed: Vec::new(),
+            locked: Vec::new(),
+        }
+    }
+
+    fn next_tx(&mut self) -> TransactionId {
+        self.tx += 1;
+        self.tx
+    }
+
+    fn client(&mut self) -> ClientId {
+        1 + self.random.below(u64::from(self.args.clients)) as ClientId
+    }
+
+    /// an amount of up to `max` minor units with zero to four decimal places
+    fn amount(&mut self, max: Amount) -> (Amount, String) {
+        let decimal_places = self.random.below(5) as u32;
+        let step = 10u64.pow(4 - decimal_places);
+        let amount = (1 + self.random.below(max / step)) * step;
+
+        let text = match decimal_places {
+            0 => (amount / SCALE).to_string(),
+            _ => format!(
+                "{}.{:0width$}",
+                amount / SCALE,
+                amount % SCALE / step,
+                width = decimal_places as usize
+            ),
+        };
+
+        (amount, text)
+    }
+
+    /// the fields of the next row
+    fn row(&mut self) -> [String; 4] {
+        if self.random.chance(self.args.invalid) {
+            return self.invalid();
+        }
+        if !self.locked.is_empty() && self.random.chance(self.args.locked) {
+            let client = self.locked[self.random.index(self.locked.len())];
+            return match self.random.chance(0.5) {
+                true => self.deposit(client),
+                false => self.withdrawal(client),
+            };
+        }
+
+        let args = self.args;
+        let weight = self.random.below(args.total_weight());
+        if weight < u64::from(args.deposits) {
+            let client = self.client();
+            self.deposit(client)
+        } else if weight < u64::from(args.deposits) + u64::from(args.withdrawals) {
+            let client = self.client();
+            self.withdrawal(client)
+        } else if !self.disputed.is_empty() && self.random.chance(0.5) {
+            self.settlement()
+        } else if !self.recent.is_empty() {
+            self.dispute()
+        } else {
+            let client = self.client();
+            self.deposit(client)
+        }
+    }
+
+    fn deposit(&mut self, client: ClientId) -> [String; 4] {
+        let tx = self.next_tx();
+        let (amount, text) = self.amount(MAX_DEPOSIT);
+        if self.model.deposit(client, tx, amount) {
+            self.booked(client, tx);
+        }
+
+        fields("deposit", client, tx, text)
+    }
+
+    fn withdrawal(&mut self, client: ClientId) -> [String; 4] {
+        let tx = self.next_tx();
+        let (amount, text) = self.amount(MAX_WITHDRAWAL);
+        if self.model.withdraw(client, tx, amount) {
+            self.booked(client, tx);
+        }
+
+        fields("withdrawal", client, tx, text)
+    }
+
+    fn booked(&mut self, client: ClientId, tx: TransactionId) {
+        if self.recent.len() == RECENT_BOOKINGS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((client, tx));
+    }
+
+    /// disputes one of the recent bookings, which might have been disputed already
+    fn dispute(&mut self) -> [String; 4] {
+        let (client, tx) = self.recent[self.random.index(self.recent.len())];
+        if self.model.dispute(client, tx) {
+            self.disputed.push((client, tx));
+        }
+
+        fields("dispute", client, tx, String::new())
+    }
+
+    /// resolves or charges back an open dispute
+    fn settlement(&mut self) -> [String; 4] {
+        let index = self.random.index(self.disputed.len());
+        let (client, tx) = self.disputed.swap_remove(index);
+
+        if !self.random.chance(self.args.chargebacks) {
+            self.model.resolve(client, tx);
+            return fields("resolve", client, tx, String::new());
+        }
+        if self.model.chargeback(client, tx) {
+            self.locked.push(client);
+        }
+
+        fields("chargeback", client, tx, String::new())
+    }
+
+    /// a row that is rejected while parsing
+    fn invalid(&mut self) -> [String; 4] {
+        let client = self.client();
+        let tx = self.next_tx();
+        let (_, amount) = self.amount(MAX_DEPOSIT);
+
+        match self.random.below(6) {
+            0 => fields("refund", client, tx, amount),
+            1 => fields("deposit", client, tx, format!("-{}", amount)),
+            2 => fields("deposit", client, tx, "1.00001".to_string()),
+            3 => fields("withdrawal", client, tx, String::new()),
+            4 => fields("dispute", client, tx, amount),
+            _ => [
+                "deposit".to_string(),
+                "client".to_string(),
+                tx.to_string(),
+                amount,
+            ],
+        }
+    }
+
+    /// writes the header and all rows
+    fn write<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "type,client,tx,amount")?;
+
+        for _ in 0..self.args.rows {
+            let mut row = self.row();
+            if self.random.chance(self.args.whitespace) {
+                for field in row.iter_mut() {
+                    let before = " ".repeat(self.random.index(3));
+                    let after = " ".repeat(self.random.index(3));
+                    *field = format!("{}{}{}", before, field, after);
+                }
+            }
+            writeln!(writer, "{}", row.join(","))?;
+        }
+
+        writer.flush()
+    }
+}
+
+fn fields(r#type: &str, client: ClientId, tx: TransactionId, amount: String) -> [String; 4] {
+    [
+        r#type.to_string(),
+        client.to_string(),
+        tx.to_string(),
+        amount,
+    ]
+}
+
+fn create(path: Option<&PathBuf>) -> Box<dyn Write> {
+    match path {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).expect("could not create given file"),
+        )),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.total_weight() == 0 {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                "at least one of the weights has to be positive",
+            )
+            .exit();
+    }
+
+    let mut generator = Generator::new(&args);
+    generator
+        .write(&mut create(args.output.as_ref()))
+        .expect("could not write rows");
+
+    if let Some(path) = args.expected.as_ref() {
+        generator
+            .model
+            .write_expected(create(Some(path)))
+            .expect("could not write expected output");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Args, Generator};
+    use clap::{CommandFactory, Parser};
+
+    fn args(arguments: &[&str]) -> Args {
+        Args::parse_from([&["generate"], arguments].concat())
+    }
+
+    fn generate(args: &Args) -> (String, String) {
+        let mut generator = Generator::new(args);
+        let mut rows = Vec::new();
+        generator.write(&mut rows).unwrap();
+        let mut expected = Vec::new();
+        generator.model.write_expected(&mut expected).unwrap();
+
+        (
+            String::from_utf8(rows).unwrap(),
+            String::from_utf8(expected).unwrap(),
+        )
+    }
+
+    #[test]
+    fn is_consistent() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn is_reproducible() {
+        let args = args(&["--rows", "1000", "--seed", "7"]);
+
+        assert_eq!(generate(&args), generate(&args));
+        assert_ne!(
+            generate(&args).0,
+            generate(&self::args(&["--rows", "1000"])).0
+        );
+    }
+}
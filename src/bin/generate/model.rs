@@ -0,0 +1,227 @@
+use booking_engine::{Amount, ClientId, Decimal, TransactionId};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Booked,
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+struct Booking {
+    amount: Amount,
+    is_deposit: bool,
+    state: State,
+}
+
+#[derive(Default)]
+struct Account {
+    available: Amount,
+    held: Amount,
+    locked: bool,
+    bookings: HashMap<TransactionId, Booking>,
+}
+
+/// a deliberately simple reimplementation of the engine for deposits, withdrawals and disputes
+///
+/// the model knows nothing about assets, transfers or administrative transactions and does not
+/// check for overflows, as the generator never produces any of them. Every operation returns
+/// whether the engine is expected to execute the transaction.
+#[derive(Default)]
+pub struct Model {
+    accounts: BTreeMap<ClientId, Account>,
+    transactions: HashSet<TransactionId>,
+}
+
+impl Model {
+    /// an existing account that accepts transactions
+    fn active(&mut self, client: ClientId) -> Option<&mut Account> {
+        self.accounts
+            .get_mut(&client)
+            .filter(|account| !account.locked)
+    }
+
+    /// credits a client, the account of a new client is created on the fly
+    pub fn deposit(&mut self, client: ClientId, tx: TransactionId, amount: Amount) -> bool {
+        if self.transactions.contains(&tx) {
+            return false;
+        }
+        let account = self.accounts.entry(client).or_default();
+        if account.locked {
+            return false;
+        }
+
+        account.available += amount;
+        account.bookings.insert(tx, Booking::new(amount, true));
+        self.transactions.insert(tx);
+        true
+    }
+
+    pub fn withdraw(&mut self, client: ClientId, tx: TransactionId, amount: Amount) -> bool {
+        if self.transactions.contains(&tx) {
+            return false;
+        }
+        let Some(account) = self.active(client) else {
+            return false;
+        };
+        if account.available < amount {
+            return false;
+        }
+
+        account.available -= amount;
+        account.bookings.insert(tx, Booking::new(amount, false));
+        self.transactions.insert(tx);
+        true
+    }
+
+    /// freezes a deposit or holds a withdrawn amount, which might be owed back
+    pub fn dispute(&mut self, client: ClientId, tx: TransactionId) -> bool {
+        let Some(account) = self.active(client) else {
+            return false;
+        };
+        let Some(booking) = account.bookings.get_mut(&tx) else {
+            return false;
+        };
+        if booking.state != State::Booked {
+            return false;
+        }
+
+        if booking.is_deposit {
+            if account.available < booking.amount {
+                return false;
+            }
+            account.available -= booking.amount;
+        }
+        account.held += booking.amount;
+        booking.state = State::Disputed;
+        true
+    }
+
+    /// releases a disputed deposit, a disputed withdrawal stands
+    pub fn resolve(&mut self, client: ClientId, tx: TransactionId) -> bool {
+        let Some(account) = self.active(client) else {
+            return false;
+        };
+        let Some(booking) = account.bookings.get_mut(&tx) else {
+            return false;
+        };
+        if booking.state != State::Disputed {
+            return false;
+        }
+
+        if booking.is_deposit {
+            account.available += booking.amount;
+        }
+        account.held -= booking.amount;
+        booking.state = State::Resolved;
+        true
+    }
+
+    /// removes a disputed deposit or pays back a disputed withdrawal and locks the client
+    pub fn chargeback(&mut self, client: ClientId, tx: TransactionId) -> bool {
+        let Some(account) = self.active(client) else {
+            return false;
+        };
+        let Some(booking) = account.bookings.get_mut(&tx) else {
+            return false;
+        };
+        if booking.state != State::Disputed {
+            return false;
+        }
+
+        if !booking.is_deposit {
+            account.available += booking.amount;
+        }
+        account.held -= booking.amount;
+        booking.state = State::ChargedBack;
+        account.locked = true;
+        true
+    }
+
+    /// writes the output the engine produces with its default options
+    pub fn write_expected<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(["client", "available", "held", "total", "locked"])?;
+
+        for (client, account) in &self.accounts {
+            let amount = |value| Decimal::new(value).to_string();
+            writer.write_record([
+                client.to_string(),
+                amount(account.available),
+                amount(account.held),
+                amount(account.available + account.held),
+                account.locked.to_string(),
+            ])?;
+        }
+
+        writer.flush()
+    }
+}
+
+impl Booking {
+    fn new(amount: Amount, is_deposit: bool) -> Self {
+        Self {
+            amount,
+            is_deposit,
+            state: State::Booked,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::Model;
+
+    fn expected(model: &Model) -> String {
+        let mut output = Vec::new();
+        model.write_expected(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn follows_the_dispute_lifecycle() {
+        let mut model = Model::default();
+
+        assert!(model.deposit(1, 1, 20_000));
+        assert!(!model.deposit(2, 1, 10_000));
+        assert!(model.withdraw(1, 2, 5_000));
+        assert!(!model.withdraw(2, 3, 5_000));
+        assert!(model.dispute(1, 2));
+        assert!(model.resolve(1, 2));
+        assert!(!model.chargeback(1, 2));
+        assert!(model.deposit(1, 3, 10_000));
+        assert!(model.dispute(1, 3));
+        assert!(model.chargeback(1, 3));
+        assert!(!model.deposit(1, 4, 10_000));
+
+        assert_eq!(
+            expected(&model),
+            concat!(
+                "client,available,held,total,locked\n",
+                "1,1.5,0.0,1.5,true\n",
+            )
+        );
+    }
+
+    #[test]
+    fn holds_disputed_withdrawals() {
+        let mut model = Model::default();
+
+        assert!(model.deposit(1, 1, 20_000));
+        assert!(model.withdraw(1, 2, 15_000));
+        // the deposit can not be frozen anymore, the withdrawal is held on top of the balance
+        assert!(!model.dispute(1, 1));
+        assert!(model.dispute(1, 2));
+
+        assert_eq!(
+            expected(&model),
+            concat!(
+                "client,available,held,total,locked\n",
+                "1,0.5,1.5,2.0,false\n",
+            )
+        );
+    }
+}
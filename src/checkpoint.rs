@@ -0,0 +1,258 @@
+use crate::payment_engine::PaymentEngine;
+use csv::Position;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
+use std::fs::{canonicalize, metadata, remove_file, rename, File};
+use std::io;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// position of the next record that has to be read from the input
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InputPosition {
+    byte: u64,
+    line: u64,
+    record: u64,
+}
+
+impl From<&Position> for InputPosition {
+    fn from(value: &Position) -> Self {
+        Self {
+            byte: value.byte(),
+            line: value.line(),
+            record: value.record(),
+        }
+    }
+}
+
+impl From<InputPosition> for Position {
+    fn from(value: InputPosition) -> Self {
+        let mut position = Position::new();
+        position
+            .set_byte(value.byte)
+            .set_line(value.line)
+            .set_record(value.record);
+        position
+    }
+}
+
+/// the input file a checkpoint has been taken of, identified by its canonical path and size
+///
+/// a checkpoint only stores a byte offset, resuming it with another file would silently continue
+/// at an arbitrary position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputFile {
+    path: PathBuf,
+    size: u64,
+}
+
+impl InputFile {
+    pub fn of(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            path: canonicalize(path)?,
+            size: metadata(path)?.len(),
+        })
+    }
+}
+
+impl Display for InputFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({} bytes)", self.path.display(), self.size)
+    }
+}
+
+/// the file a checkpoint is written to before it is moved into place
+fn temporary(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".tmp");
+
+    name.into()
+}
+
+/// a snapshot of a partially processed input file
+///
+/// contains the full engine state together with the position the input has been consumed to,
+/// so that processing can be resumed with exactly the same result as an uninterrupted run.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    input: InputFile,
+    position: InputPosition,
+    /// length of the rejects report at the time of the checkpoint
+    rejects_offset: u64,
+    engine: PaymentEngine,
+}
+
+impl Checkpoint {
+    pub fn input(&self) -> &InputFile {
+        &self.input
+    }
+
+    pub fn position(&self) -> Position {
+        self.position.into()
+    }
+
+    pub fn rejects_offset(&self) -> u64 {
+        self.rejects_offset
+    }
+
+    pub fn into_engine(self) -> PaymentEngine {
+        self.engine
+    }
+
+    /// persists a checkpoint without consuming the engine
+    ///
+    /// the checkpoint is written to a temporary file first and then moved into place, so that a
+    /// process killed while writing never leaves a corrupt checkpoint behind.
+    pub fn save(
+        path: &Path,
+        input: &InputFile,
+        position: &Position,
+        rejects_offset: u64,
+        engine: &PaymentEngine,
+    ) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct CheckpointRef<'a> {
+            input: &'a InputFile,
+            position: InputPosition,
+            rejects_offset: u64,
+            engine: &'a PaymentEngine,
+        }
+
+        let temporary = temporary(path);
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        serde_json::to_writer(
+            &mut writer,
+            &CheckpointRef {
+                input,
+                position: position.into(),
+                rejects_offset,
+                engine,
+            },
+        )?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+
+        rename(temporary, path)
+    }
+
+    /// checks that a checkpoint can be saved to the given path, without touching an existing one
+    pub fn check_writable(path: &Path) -> io::Result<()> {
+        let temporary = temporary(path);
+        File::create(&temporary)?;
+
+        remove_file(temporary)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checkpoint::{temporary, Checkpoint, InputFile};
+    use crate::payment_engine::{PaymentEngine, Transaction};
+    use crate::record::CsvTransactionItem;
+    use crate::test_support::snapshot;
+    use csv::{Reader, ReaderBuilder, StringRecord};
+    use std::env::temp_dir;
+    use std::io::Cursor;
+    use std::path::Path;
+
+    const INPUT: &str = "type,client,tx,amount
+deposit,1,1,10.0
+deposit,2,2,5.0
+withdrawal,1,3,2.5
+dispute,1,1,
+deposit,1,3,1.0
+resolve,1,1,
+dispute,2,2,
+chargeback,2,2,
+deposit,2,4,1.0
+withdrawal,1,5,1.0
+";
+
+    fn reader() -> Reader<Cursor<&'static str>> {
+        ReaderBuilder::new().from_reader(Cursor::new(INPUT))
+    }
+
+    /// executes records until the reader is exhausted or the given number of records is read
+    fn process(reader: &mut Reader<Cursor<&str>>, engine: &mut PaymentEngine, limit: usize) {
+        let headers = reader.headers().unwrap().clone();
+        let mut record = StringRecord::new();
+        for _ in 0..limit {
+            if !reader.read_record(&mut record).unwrap() {
+                break;
+            }
+            let item: CsvTransactionItem = record.deserialize(Some(&headers)).unwrap();
+            let _ = engine.execute(Transaction::try_from(item).unwrap());
+        }
+    }
+
+    #[test]
+    fn resumed_run_matches_uninterrupted_run() {
+        let mut engine = PaymentEngine::default();
+        process(&mut reader(), &mut engine, usize::MAX);
+        let expected = snapshot(engine.into_clients().unwrap());
+        let input = InputFile {
+            path: "input.csv".into(),
+            size: INPUT.len() as u64,
+        };
+
+        for interrupt_after in 0..10 {
+            let path = temp_dir().join(format!(
+                "payment_engine_checkpoint_{}_{}.json",
+                std::process::id(),
+                interrupt_after
+            ));
+
+            let mut first_reader = reader();
+            let mut engine = PaymentEngine::default();
+            process(&mut first_reader, &mut engine, interrupt_after);
+            Checkpoint::save(&path, &input, first_reader.position(), 7, &engine).unwrap();
+            drop(engine);
+
+            let checkpoint = Checkpoint::load(&path).unwrap();
+            assert_eq!(checkpoint.input(), &input);
+            assert_eq!(checkpoint.rejects_offset(), 7);
+            assert_eq!(checkpoint.position(), *first_reader.position());
+
+            let mut second_reader = reader();
+            second_reader.headers().unwrap();
+            second_reader.seek(checkpoint.position()).unwrap();
+            let mut engine = checkpoint.into_engine();
+            process(&mut second_reader, &mut engine, usize::MAX);
+
+            assert_eq!(snapshot(engine.into_clients().unwrap()), expected);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn writes_next_to_the_checkpoint() {
+        assert_eq!(
+            temporary(Path::new("state.json")),
+            Path::new("state.json.tmp")
+        );
+        assert_eq!(
+            temporary(Path::new("state.tmp")),
+            Path::new("state.tmp.tmp")
+        );
+    }
+
+    #[test]
+    fn checks_the_checkpoint_directory() {
+        let path = temp_dir().join(format!(
+            "payment_engine_checkpoint_{}_writable.json",
+            std::process::id()
+        ));
+
+        Checkpoint::check_writable(&path).unwrap();
+        assert!(!temporary(&path).exists());
+        assert!(!path.exists());
+        assert!(Checkpoint::check_writable(&path.join("missing").join("state.json")).is_err());
+    }
+}
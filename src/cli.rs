@@ -0,0 +1,369 @@
+use booking_engine::{Column, DiskStore, DisputeWindow};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, ValueEnum};
+use log::LevelFilter;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+/// the input name that reads from stdin instead of a file
+pub const STDIN: &str = "-";
+
+/// number of records processed between two checkpoints if not configured otherwise
+const DEFAULT_CHECKPOINT_INTERVAL: u64 = 100_000;
+
+/// number of clients a disk store keeps in memory if not configured otherwise
+const DEFAULT_STORE_CACHE: usize = 10_000;
+
+/// the format of the input rows or the output clients
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    #[default]
+    Csv,
+    /// one json object per line
+    Jsonl,
+}
+
+/// the order of the output rows
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SortOrder {
+    /// ascending by client id and asset
+    #[default]
+    Client,
+    /// descending by the total balance, ties ordered by client id and asset
+    Total,
+    /// locked clients first, each group ordered by client id and asset
+    Locked,
+}
+
+/// processes transaction files and writes the final balance of every client
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// csv files processed in the given order into the same engine, `-` reads from stdin
+    #[arg(value_name = "FILE", required_unless_present = "replay")]
+    pub inputs: Vec<String>,
+
+    /// the format of all inputs
+    #[arg(long, value_name = "FORMAT", default_value_t, value_enum)]
+    pub input_format: Format,
+
+    /// writes the clients to the given file instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// the format of the written clients
+    #[arg(long, value_name = "FORMAT", default_value_t, value_enum)]
+    pub output_format: Format,
+
+    /// the order of the written clients
+    #[arg(long, value_name = "ORDER", default_value_t, value_enum)]
+    pub sort: SortOrder,
+
+    /// comma separated columns to write in the given order, all but `status` and `lock_reason`
+    /// by default
+    #[arg(
+        long,
+        value_name = "COLUMNS",
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(Column::ALL.map(|column| column.name()))
+            .map(|name| Column::from_name(&name).expect("only column names are possible"))
+    )]
+    pub columns: Vec<Column>,
+
+    /// adds the `status` and `lock_reason` columns to the default columns
+    #[arg(long, conflicts_with = "columns")]
+    pub status: bool,
+
+    /// writes amounts with exactly four decimal places (e.g. `1.5000`)
+    #[arg(long)]
+    pub fixed_decimals: bool,
+
+    /// minimum level of the logged warnings, overrides `RUST_LOG`
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<LevelFilter>,
+
+    /// writes all rejected rows to the given csv report
+    #[arg(long, value_name = "PATH")]
+    pub rejects: Option<PathBuf>,
+
+    /// processes the clients on N worker threads
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["checkpoint", "resume", "journal", "admin_log", "replay"]
+    )]
+    pub threads: Option<NonZeroUsize>,
+
+    /// persists the engine state to the given file every `--checkpoint-every` records
+    #[arg(long, value_name = "PATH")]
+    pub checkpoint: Option<PathBuf>,
+
+    /// number of records processed between two checkpoints
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = DEFAULT_CHECKPOINT_INTERVAL,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub checkpoint_every: u64,
+
+    /// continues processing the input from the given checkpoint
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["journal", "admin_log", "replay"])]
+    pub resume: Option<PathBuf>,
+
+    /// writes every executed transaction as an event to the given JSON lines journal
+    #[arg(long, value_name = "PATH")]
+    pub journal: Option<PathBuf>,
+
+    /// writes administrative events to the given JSON lines log
+    #[arg(long, value_name = "PATH")]
+    pub admin_log: Option<PathBuf>,
+
+    /// rebuilds the state from the given journal before processing any input
+    #[arg(long, value_name = "PATH")]
+    pub replay: Option<PathBuf>,
+
+    /// keeps the clients in the given file instead of memory, the file is overwritten
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["threads", "checkpoint", "resume", "replay"]
+    )]
+    pub store: Option<PathBuf>,
+
+    /// evicts undisputed bookings after the given number of subsequently executed transactions
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["dispute_window_age", "threads", "journal", "replay"]
+    )]
+    pub dispute_window: Option<u64>,
+
+    /// evicts undisputed bookings once the `timestamp` column is more than the given age ahead
+    #[arg(
+        long,
+        value_name = "AGE",
+        conflicts_with_all = ["threads", "journal", "replay"]
+    )]
+    pub dispute_window_age: Option<u64>,
+
+    /// number of clients the `--store` keeps in memory
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = DEFAULT_STORE_CACHE,
+        value_parser = clap::value_parser!(u64)
+            .range(DiskStore::MIN_CAPACITY as u64..)
+            .map(|n| n as usize)
+    )]
+    pub store_cache: usize,
+}
+
+impl Cli {
+    /// parses the command line arguments, exits with a usage error if they are invalid
+    pub fn parse_valid() -> Self {
+        let cli = Self::parse();
+        if let Err(e) = cli.validate() {
+            e.exit();
+        }
+
+        cli
+    }
+
+    /// the dispute window given by either `--dispute-window` or `--dispute-window-age`
+    pub fn dispute_window(&self) -> Option<DisputeWindow> {
+        self.dispute_window
+            .map(DisputeWindow::Transactions)
+            .or(self.dispute_window_age.map(DisputeWindow::Age))
+    }
+
+    /// checks the constraints that can not be expressed by the argument definitions
+    fn validate(&self) -> Result<(), clap::Error> {
+        // stdin can only be read once, all inputs are opened before processing
+        if self.inputs.iter().filter(|input| *input == STDIN).count() > 1 {
+            return Err(Self::command().error(
+                ErrorKind::ArgumentConflict,
+                "stdin can only be given once as input",
+            ));
+        }
+
+        // a checkpoint stores the position within a single, seekable input
+        let is_checkpointing = self.checkpoint.is_some() || self.resume.is_some();
+        let is_single_file = self.inputs.len() == 1 && self.inputs[0] != STDIN;
+        if is_checkpointing && !is_single_file {
+            return Err(Self::command().error(
+                ErrorKind::ArgumentConflict,
+                "checkpointing requires a single input file",
+            ));
+        }
+        if is_checkpointing && self.input_format != Format::Csv {
+            return Err(Self::command().error(
+                ErrorKind::ArgumentConflict,
+                "checkpointing is only supported for csv input",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{Cli, Format, SortOrder};
+    use booking_engine::Column;
+    use booking_engine::DisputeWindow;
+    use clap::error::ErrorKind;
+    use clap::{CommandFactory, Parser};
+
+    fn parse(arguments: &[&str]) -> Result<Cli, ErrorKind> {
+        let cli =
+            Cli::try_parse_from([&["booking_engine"], arguments].concat()).map_err(|e| e.kind())?;
+        cli.validate().map_err(|e| e.kind())?;
+
+        Ok(cli)
+    }
+
+    #[test]
+    fn is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn accepts_multiple_inputs() {
+        let cli = parse(&["a.csv", "-", "b.csv", "--output", "out.csv"]).unwrap();
+
+        assert_eq!(cli.inputs, vec!["a.csv", "-", "b.csv"]);
+        assert_eq!(cli.output.unwrap().to_str(), Some("out.csv"));
+        assert_eq!(cli.checkpoint_every, 100_000);
+        assert_eq!(cli.input_format, Format::Csv);
+        assert_eq!(cli.sort, SortOrder::Client);
+        assert!(cli.columns.is_empty());
+    }
+
+    #[test]
+    fn accepts_output_options() {
+        let cli = parse(&[
+            "a.csv",
+            "--sort",
+            "locked",
+            "--columns",
+            "client,lock_reason",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.sort, SortOrder::Locked);
+        assert_eq!(cli.columns, vec![Column::Client, Column::LockReason]);
+        assert_eq!(
+            parse(&["a.csv", "--columns", "client,balance"]).unwrap_err(),
+            ErrorKind::InvalidValue
+        );
+        assert!(parse(&["a.csv", "--status"]).unwrap().status);
+        assert_eq!(
+            parse(&["a.csv", "--status", "--columns", "client"]).unwrap_err(),
+            ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    fn accepts_formats() {
+        let cli = parse(&[
+            "a.jsonl",
+            "--input-format",
+            "jsonl",
+            "--output-format",
+            "jsonl",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.input_format, Format::Jsonl);
+        assert_eq!(cli.output_format, Format::Jsonl);
+        assert_eq!(
+            parse(&["a.csv", "--output-format", "xml"]).unwrap_err(),
+            ErrorKind::InvalidValue
+        );
+    }
+
+    #[test]
+    fn accepts_dispute_windows() {
+        assert_eq!(parse(&["a.csv"]).unwrap().dispute_window(), None);
+        assert_eq!(
+            parse(&["a.csv", "--dispute-window", "100"])
+                .unwrap()
+                .dispute_window(),
+            Some(DisputeWindow::Transactions(100))
+        );
+        assert_eq!(
+            parse(&["a.csv", "--dispute-window-age", "3600"])
+                .unwrap()
+                .dispute_window(),
+            Some(DisputeWindow::Age(3600))
+        );
+        assert_eq!(
+            parse(&["a.csv", "--dispute-window", "0"]).unwrap_err(),
+            ErrorKind::ValueValidation
+        );
+    }
+
+    #[test]
+    fn requires_input_unless_replaying() {
+        assert_eq!(parse(&[]).unwrap_err(), ErrorKind::MissingRequiredArgument);
+        assert!(parse(&["--replay", "journal.jsonl"]).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_combinations() {
+        let conflicts: [&[&str]; 9] = [
+            &["-", "a.csv", "-"],
+            &["a.csv", "--threads", "2", "--journal", "journal.jsonl"],
+            &[
+                "a.csv",
+                "--resume",
+                "state.json",
+                "--admin-log",
+                "admin.jsonl",
+            ],
+            &["a.csv", "b.csv", "--checkpoint", "state.json"],
+            &["-", "--resume", "state.json"],
+            &[
+                "a.jsonl",
+                "--input-format",
+                "jsonl",
+                "--checkpoint",
+                "state.json",
+            ],
+            &["a.csv", "--store", "clients.jsonl", "--threads", "2"],
+            &[
+                "a.csv",
+                "--dispute-window",
+                "10",
+                "--dispute-window-age",
+                "10",
+            ],
+            &[
+                "a.csv",
+                "--dispute-window-age",
+                "10",
+                "--journal",
+                "journal.jsonl",
+            ],
+        ];
+        for arguments in conflicts {
+            assert_eq!(parse(arguments).unwrap_err(), ErrorKind::ArgumentConflict);
+        }
+
+        assert_eq!(
+            parse(&["a.csv", "--threads", "0"]).unwrap_err(),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            parse(&["a.csv", "--checkpoint-every", "0"]).unwrap_err(),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            parse(&["a.csv", "--store", "clients.jsonl", "--store-cache", "1"]).unwrap_err(),
+            ErrorKind::ValueValidation
+        );
+    }
+}
@@ -1,27 +1,40 @@
+use crate::asset::Asset;
 use crate::balance::{Amount, ArithmeticError, Balance};
-use crate::client::ExecutionError::{InvalidBooking, InvalidState};
+use crate::client::ExecutionError::{
+    Arithmetic, ClientClosed, ClientDoesNotExist, ClientLocked, DisputeWindowExpired,
+    DuplicateTransaction, InvalidBooking, InvalidState, InvalidStatus, JournalUnavailable,
+    NonZeroBalance, SelfTransfer, StoreUnavailable,
+};
 use crate::client::State::{Booked, Chargeback, Disputed, Resolved};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 
 pub type ClientId = u16;
 pub type TransactionId = u32;
 
-type BookingMap = HashMap<TransactionId, BookedDeposit>;
+type BalanceMap = HashMap<Asset, Balance>;
+type BookingMap = HashMap<TransactionId, BookedTransaction>;
 
-#[derive(Clone)]
+/// a client holding one balance per asset
+///
+/// bookings are kept in a single map as transaction ids are globally unique, every booking knows
+/// the asset it has been booked in. The account status applies to the client as a whole.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Client {
     id: ClientId,
-    balance: Balance,
+    balances: BalanceMap,
     bookings: BookingMap,
-    locked: bool,
+    status: AccountStatus,
 }
 
 impl Client {
-    pub fn new(id: ClientId) -> Self {
+    pub(crate) fn new(id: ClientId) -> Self {
         Self {
             id,
-            locked: false,
-            balance: Balance::default(),
+            status: AccountStatus::Active,
+            balances: BalanceMap::default(),
             bookings: BookingMap::default(),
         }
     }
@@ -30,32 +43,121 @@ impl Client {
         self.id
     }
 
-    pub fn balance(&self) -> &Balance {
-        &self.balance
+    /// returns the balance of the given asset if the client has ever booked a transaction in it
+    pub fn balance(&self, asset: &Asset) -> Option<&Balance> {
+        self.balances.get(asset)
     }
 
-    pub fn lock(&mut self) {
-        self.locked = true;
+    /// iterates over all balances of the client ordered by asset
+    pub fn balances(&self) -> impl Iterator<Item = (&Asset, &Balance)> {
+        let mut balances: Vec<_> = self.balances.iter().collect();
+        balances.sort_by_key(|(asset, _)| **asset);
+        balances.into_iter()
+    }
+
+    pub(crate) fn lock(&mut self, reason: LockReason) {
+        self.status = AccountStatus::Locked(reason);
+    }
+
+    pub(crate) fn set_status(&mut self, status: AccountStatus) {
+        self.status = status;
+    }
+
+    pub fn status(&self) -> AccountStatus {
+        self.status
     }
 
     pub fn is_locked(&self) -> bool {
-        self.locked
+        self.status.is_locked()
+    }
+
+    /// checks that the client accepts transactions other than administrative ones
+    pub(crate) fn ensure_active(&self) -> Result<(), ExecutionError> {
+        match self.status {
+            AccountStatus::Active => Ok(()),
+            AccountStatus::Locked(_) => Err(ClientLocked),
+            AccountStatus::Closed => Err(ClientClosed),
+        }
+    }
+
+    /// checks whether the client holds no funds in any asset
+    pub fn has_zero_balance(&self) -> bool {
+        self.balances
+            .values()
+            .all(|balance| balance.available() == 0 && balance.frozen() == 0)
     }
 
-    pub fn get_booking_mut(&mut self, tx_id: &TransactionId) -> Result<&mut BookedDeposit, ExecutionError> {
-        self.bookings.get_mut(tx_id).ok_or(InvalidBooking)
+    pub fn get_booking(&self, tx_id: &TransactionId) -> Result<&BookedTransaction, ExecutionError> {
+        self.bookings.get(tx_id).ok_or(InvalidBooking)
     }
 
-    pub fn add_or_update_booking(&mut self, deposit: BookedDeposit) {
-        self.bookings.insert(deposit.tx, deposit);
+    /// iterates over all booked transactions of the client in arbitrary order
+    pub fn bookings(&self) -> impl Iterator<Item = &BookedTransaction> {
+        self.bookings.values()
+    }
+
+    /// evicts a booking that is still in [State::Booked], it can not be disputed anymore
+    ///
+    /// bookings that are (or have been) disputed are kept, so that their dispute can be settled.
+    pub(crate) fn expire_booking(&mut self, tx_id: TransactionId) {
+        if self.bookings.get(&tx_id).map(|booking| &booking.state) == Some(&Booked) {
+            self.bookings.remove(&tx_id);
+        }
     }
 
-    pub fn get_balance_mut(&mut self) -> &mut Balance {
-        &mut self.balance
+    pub(crate) fn add_or_update_booking(&mut self, booking: BookedTransaction) {
+        self.bookings.insert(booking.tx, booking);
     }
+
+    pub(crate) fn get_balance_mut(&mut self, asset: Asset) -> &mut Balance {
+        self.balances.entry(asset).or_default()
+    }
+}
+
+/// whether a client accepts transactions
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    /// only administrative transactions are accepted until the client is unlocked
+    Locked(LockReason),
+    /// the account has been closed for good
+    Closed,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl AccountStatus {
+    pub fn is_locked(&self) -> bool {
+        matches!(self, AccountStatus::Locked(_))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountStatus::Active => "active",
+            AccountStatus::Locked(_) => "locked",
+            AccountStatus::Closed => "closed",
+        }
+    }
+}
+
+/// why a client has been locked
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LockReason {
+    /// the client charged back a transaction
+    Chargeback,
+    /// the client has been locked by an administrator
+    Manual,
+}
+
+impl LockReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LockReason::Chargeback => "chargeback",
+            LockReason::Manual => "manual",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum State {
     Booked,
     Disputed,
@@ -63,39 +165,80 @@ pub enum State {
     Chargeback,
 }
 
-#[derive(Clone)]
-pub struct BookedDeposit {
+/// the direction in which a booked transaction moved funds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Direction {
+    /// funds were credited to the client (deposit or received transfer)
+    Credit,
+    /// funds were debited from the client (withdrawal or sent transfer)
+    Debit,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BookedTransaction {
     tx: TransactionId,
+    asset: Asset,
     amount: Amount,
+    direction: Direction,
+    /// the other client of a transfer
+    counterparty: Option<ClientId>,
     state: State,
 }
 
-impl BookedDeposit {
-    pub fn new(tx: TransactionId, amount: Amount) -> Self {
+impl BookedTransaction {
+    pub(crate) fn new(
+        tx: TransactionId,
+        asset: Asset,
+        amount: Amount,
+        direction: Direction,
+    ) -> Self {
         Self {
             state: Booked,
             tx,
+            asset,
             amount,
+            direction,
+            counterparty: None,
         }
     }
 
-    pub fn dispute(&mut self) -> Result<(), ExecutionError> {
+    pub(crate) fn with_counterparty(mut self, counterparty: Option<ClientId>) -> Self {
+        self.counterparty = counterparty;
+        self
+    }
+
+    pub(crate) fn dispute(&mut self) -> Result<(), ExecutionError> {
         self.try_change_state(Booked, Disputed)
     }
 
-    pub fn resolve(&mut self) -> Result<(), ExecutionError> {
+    pub(crate) fn resolve(&mut self) -> Result<(), ExecutionError> {
         self.try_change_state(Disputed, Resolved)
     }
 
-    pub fn chargeback(&mut self) -> Result<(), ExecutionError> {
+    pub(crate) fn chargeback(&mut self) -> Result<(), ExecutionError> {
         self.try_change_state(Disputed, Chargeback)
     }
 
+    pub fn tx(&self) -> TransactionId {
+        self.tx
+    }
+
+    pub fn asset(&self) -> Asset {
+        self.asset
+    }
+
     pub fn amount(&self) -> Amount {
         self.amount
     }
 
-    #[allow(dead_code)]
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn counterparty(&self) -> Option<ClientId> {
+        self.counterparty
+    }
+
     pub fn state(&self) -> &State {
         &self.state
     }
@@ -111,18 +254,67 @@ impl BookedDeposit {
 }
 
 #[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum ExecutionError {
     InvalidState,
     InvalidBooking,
     ClientLocked,
+    ClientClosed,
     ClientDoesNotExist,
+    DuplicateTransaction,
+    SelfTransfer,
+    /// an administrative transaction does not apply to the current account status
+    InvalidStatus,
+    /// a client can only be closed once it holds no funds
+    NonZeroBalance,
+    JournalUnavailable,
+    /// the referenced booking has been evicted as its dispute window expired
+    DisputeWindowExpired,
+    /// a client could not be loaded from (or room could not be made in) the [ClientStore]
+    ///
+    /// [ClientStore]: crate::ClientStore
+    StoreUnavailable,
     Arithmetic(ArithmeticError),
 }
 
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidState => write!(f, "booking is not in the required state"),
+            InvalidBooking => write!(f, "referenced booking does not exist"),
+            ClientLocked => write!(f, "client is locked"),
+            ClientClosed => write!(f, "client is closed"),
+            ClientDoesNotExist => write!(f, "client does not exist"),
+            DuplicateTransaction => write!(f, "transaction id has already been used"),
+            SelfTransfer => write!(f, "client cannot transfer funds to itself"),
+            InvalidStatus => write!(f, "client account is not in the required status"),
+            NonZeroBalance => write!(f, "client still holds funds"),
+            JournalUnavailable => write!(f, "transaction could not be written to the journal"),
+            DisputeWindowExpired => write!(f, "dispute window of the referenced booking expired"),
+            StoreUnavailable => write!(f, "client could not be loaded from the store"),
+            Arithmetic(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// an arithmetic error is already part of the message, so it is not reported as source as well
+impl Error for ExecutionError {}
+
 #[cfg(test)]
 mod tests {
+    use crate::asset::Asset;
+    use crate::balance::ArithmeticError::Overflow;
     use crate::client::State::*;
-    use crate::client::{BookedDeposit, State};
+    use crate::client::{BookedTransaction, Direction, ExecutionError, State};
+    use std::error::Error;
+
+    #[test]
+    fn reports_arithmetic_errors_once() {
+        let e = ExecutionError::Arithmetic(Overflow);
+
+        assert_eq!(e.to_string(), "amount overflow");
+        assert!(e.source().is_none());
+    }
 
     #[test]
     fn dispute() {
@@ -156,11 +348,14 @@ mod tests {
         assert_eq!(deposit.state, Chargeback);
     }
 
-    fn deposit_with_state(state: State) -> BookedDeposit {
-        BookedDeposit {
+    fn deposit_with_state(state: State) -> BookedTransaction {
+        BookedTransaction {
             state,
             amount: 0,
             tx: 0,
+            asset: Asset::default(),
+            direction: Direction::Credit,
+            counterparty: None,
         }
     }
 }
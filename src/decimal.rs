@@ -0,0 +1,215 @@
+use crate::balance::Amount;
+use crate::decimal::DecimalError::{Malformed, Negative, OutOfRange, TooManyDecimals};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// number of fractional digits an [Amount] carries
+pub const PRECISION: usize = 4;
+
+/// factor between a decimal value and its [Amount] representation
+pub const SCALE: Amount = 10u64.pow(PRECISION as u32);
+
+#[derive(Debug, PartialEq)]
+pub enum DecimalError {
+    Malformed,
+    Negative,
+    TooManyDecimals,
+    OutOfRange,
+}
+
+impl Display for DecimalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Malformed => write!(f, "amount is not a valid decimal number"),
+            Negative => write!(f, "amount must not be negative"),
+            TooManyDecimals => write!(f, "amount has more than {PRECISION} decimal places"),
+            OutOfRange => write!(f, "amount exceeds the maximum of u64::MAX / {SCALE}"),
+        }
+    }
+}
+
+impl std::error::Error for DecimalError {}
+
+/// a fixed point decimal with [PRECISION] fractional digits
+///
+/// wraps an [Amount] in minor units so that the textual representation can be converted
+/// without ever going through a floating point number.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decimal(Amount);
+
+impl Decimal {
+    pub fn new(minor_units: Amount) -> Self {
+        Self(minor_units)
+    }
+
+    pub fn minor_units(&self) -> Amount {
+        self.0
+    }
+
+    /// formats the decimal with all [PRECISION] fractional digits (e.g. `1.5000`)
+    pub fn fixed(&self) -> impl Display {
+        Fixed(self.0)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = DecimalError;
+
+    /// parses a decimal string (e.g. `1.5`) into minor units (e.g. `15000`)
+    ///
+    /// # Errors
+    /// - [Negative] if the value starts with a `-`
+    /// - [TooManyDecimals] if more than [PRECISION] fractional digits are given
+    /// - [OutOfRange] if the value does not fit into an [Amount] after scaling
+    /// - [Malformed] for any other invalid input
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('-') {
+            return Err(Negative);
+        }
+
+        let (integer, fraction) = s.split_once('.').unwrap_or((s, ""));
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if (integer.is_empty() && fraction.is_empty())
+            || !is_digits(integer)
+            || !is_digits(fraction)
+        {
+            return Err(Malformed);
+        }
+
+        if fraction.len() > PRECISION {
+            return Err(TooManyDecimals);
+        }
+
+        // leading zeros do not change the value but would otherwise overflow the parser
+        let integer = integer.trim_start_matches('0');
+        let integer: Amount = if integer.is_empty() {
+            0
+        } else {
+            integer.parse().map_err(|_| OutOfRange)?
+        };
+
+        // right-pad the fraction so that e.g. `.5` becomes `5000`
+        let fraction: Amount = if fraction.is_empty() {
+            0
+        } else {
+            format!("{fraction:0<PRECISION$}")
+                .parse()
+                .map_err(|_| Malformed)?
+        };
+
+        integer
+            .checked_mul(SCALE)
+            .and_then(|scaled| scaled.checked_add(fraction))
+            .map(Decimal)
+            .ok_or(OutOfRange)
+    }
+}
+
+impl Display for Decimal {
+    /// formats the decimal without trailing zeros but with at least one fractional digit
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let integer = self.0 / SCALE;
+        let fraction = format!("{:0>PRECISION$}", self.0 % SCALE);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            write!(f, "{integer}.0")
+        } else {
+            write!(f, "{integer}.{fraction}")
+        }
+    }
+}
+
+/// a decimal formatted with all fractional digits, see [Decimal::fixed]
+struct Fixed(Amount);
+
+impl Display for Fixed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:0>PRECISION$}", self.0 / SCALE, self.0 % SCALE)
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::balance::Amount;
+    use crate::decimal::Decimal;
+    use crate::decimal::DecimalError::{Malformed, Negative, OutOfRange, TooManyDecimals};
+
+    fn parse(value: &str) -> Result<Amount, crate::decimal::DecimalError> {
+        value.parse::<Decimal>().map(|d| d.minor_units())
+    }
+
+    #[test]
+    fn parses_into_minor_units() {
+        assert_eq!(parse("1"), Ok(10000));
+        assert_eq!(parse("1.5"), Ok(15000));
+        assert_eq!(parse("1."), Ok(10000));
+        assert_eq!(parse(".5"), Ok(5000));
+        assert_eq!(parse("0.0001"), Ok(1));
+        assert_eq!(parse("00012.3400"), Ok(123400));
+    }
+
+    #[test]
+    fn parses_up_to_the_maximum() {
+        assert_eq!(parse("1844674407370955.1615"), Ok(Amount::MAX));
+        assert_eq!(parse("1844674407370955.1616"), Err(OutOfRange));
+        assert_eq!(parse("1844674407370956"), Err(OutOfRange));
+        assert_eq!(parse("99999999999999999999999"), Err(OutOfRange));
+    }
+
+    #[test]
+    fn rejects_negative_values() {
+        assert_eq!(parse("-1.0"), Err(Negative));
+    }
+
+    #[test]
+    fn rejects_too_many_decimals() {
+        assert_eq!(parse("1.00001"), Err(TooManyDecimals));
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        assert_eq!(parse(""), Err(Malformed));
+        assert_eq!(parse("."), Err(Malformed));
+        assert_eq!(parse("1.2.3"), Err(Malformed));
+        assert_eq!(parse("1e5"), Err(Malformed));
+        assert_eq!(parse("NaN"), Err(Malformed));
+        assert_eq!(parse("+1"), Err(Malformed));
+    }
+
+    #[test]
+    fn formats_without_trailing_zeros() {
+        assert_eq!(Decimal::new(0).to_string(), "0.0");
+        assert_eq!(Decimal::new(15000).to_string(), "1.5");
+        assert_eq!(Decimal::new(10001).to_string(), "1.0001");
+        assert_eq!(
+            Decimal::new(Amount::MAX).to_string(),
+            "1844674407370955.1615"
+        );
+    }
+
+    #[test]
+    fn formats_all_decimal_places_if_fixed() {
+        assert_eq!(Decimal::new(0).fixed().to_string(), "0.0000");
+        assert_eq!(Decimal::new(15000).fixed().to_string(), "1.5000");
+        assert_eq!(Decimal::new(10001).fixed().to_string(), "1.0001");
+        assert_eq!(
+            Decimal::new(Amount::MAX).fixed().to_string(),
+            "1844674407370955.1615"
+        );
+    }
+}
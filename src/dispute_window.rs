@@ -0,0 +1,170 @@
+use crate::client::{ClientId, TransactionId};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// how long a deposit, withdrawal or transfer can be disputed after it has been booked
+///
+/// bookings that are still [Booked] once they fall outside of the window are evicted, a later
+/// dispute fails with [DisputeWindowExpired].
+///
+/// [Booked]: crate::State::Booked
+/// [DisputeWindowExpired]: crate::ExecutionError::DisputeWindowExpired
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisputeWindow {
+    /// a booking can be disputed by the given number of subsequently executed transactions
+    Transactions(u64),
+    /// a booking can be disputed until the input timestamp is more than the given age ahead of
+    /// the booking's timestamp
+    Age(u64),
+}
+
+/// a booking that may still be disputable
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub(crate) struct RecentBooking {
+    pub tx: TransactionId,
+    pub client: ClientId,
+    /// the other client of a transfer, which holds a booking as well
+    pub counterparty: Option<ClientId>,
+    /// the number of transactions executed before this one
+    sequence: u64,
+    timestamp: u64,
+}
+
+/// all bookings in the order they have been booked, together with the clock of the window
+///
+/// the clock consists of the number of executed transactions and the latest input timestamp,
+/// so bookings expire in the same order as they have been booked. Only successfully executed
+/// transactions move the clock, a rejected one can not expire any booking.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct RecentBookings {
+    bookings: VecDeque<RecentBooking>,
+    executed: u64,
+    now: u64,
+}
+
+impl RecentBookings {
+    /// the clock as seen by a transaction with the given timestamp, it never goes backwards
+    fn at(&self, timestamp: Option<u64>) -> u64 {
+        self.now.max(timestamp.unwrap_or_default())
+    }
+
+    /// counts a successfully executed transaction and moves the clock to its timestamp
+    pub fn executed(&mut self, timestamp: Option<u64>) {
+        self.executed += 1;
+        self.now = self.at(timestamp);
+    }
+
+    /// adds a booking of the transaction with the given timestamp that is executed right now
+    pub fn push(
+        &mut self,
+        tx: TransactionId,
+        client: ClientId,
+        counterparty: Option<ClientId>,
+        timestamp: Option<u64>,
+    ) {
+        self.bookings.push_back(RecentBooking {
+            tx,
+            client,
+            counterparty,
+            sequence: self.executed,
+            timestamp: self.at(timestamp),
+        });
+    }
+
+    /// checks whether a booking falls outside of the window at the time of a transaction
+    ///
+    /// the transaction has not moved the clock yet, but must not dispute a booking that it
+    /// would expire itself. Subsequent transactions are only counted once they are executed.
+    pub fn expires(
+        &self,
+        tx: TransactionId,
+        window: DisputeWindow,
+        timestamp: Option<u64>,
+    ) -> bool {
+        let DisputeWindow::Age(age) = window else {
+            return false;
+        };
+        let now = self.at(timestamp);
+
+        self.bookings
+            .iter()
+            .take_while(|booking| now - booking.timestamp > age)
+            .any(|booking| booking.tx == tx)
+    }
+
+    /// the oldest booking if it fell outside of the window
+    pub fn expired(&self, window: DisputeWindow) -> Option<RecentBooking> {
+        let booking = self.bookings.front()?;
+        let is_expired = match window {
+            DisputeWindow::Transactions(count) => self.executed - booking.sequence > count,
+            DisputeWindow::Age(age) => self.now - booking.timestamp > age,
+        };
+
+        is_expired.then_some(*booking)
+    }
+
+    /// removes the oldest booking once it has been evicted
+    pub fn pop(&mut self) {
+        self.bookings.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dispute_window::{DisputeWindow, RecentBookings};
+
+    #[test]
+    fn expires_after_subsequent_transactions() {
+        let mut recent = RecentBookings::default();
+        recent.push(1, 1, None, None);
+        recent.executed(None);
+
+        // the booked transaction itself does not count
+        assert!(recent.expired(DisputeWindow::Transactions(1)).is_none());
+
+        // the next transaction was the only one that could have disputed it
+        recent.executed(None);
+        assert_eq!(
+            recent.expired(DisputeWindow::Transactions(1)).unwrap().tx,
+            1
+        );
+        recent.pop();
+        assert!(recent.expired(DisputeWindow::Transactions(1)).is_none());
+    }
+
+    #[test]
+    fn expires_after_age() {
+        let mut recent = RecentBookings::default();
+        recent.push(1, 1, None, Some(100));
+        recent.executed(Some(100));
+
+        recent.executed(Some(110));
+        assert!(recent.expired(DisputeWindow::Age(10)).is_none());
+
+        // older timestamps do not turn back the clock
+        recent.executed(Some(50));
+        recent.executed(None);
+        assert!(recent.expired(DisputeWindow::Age(10)).is_none());
+
+        recent.executed(Some(111));
+        assert_eq!(recent.expired(DisputeWindow::Age(10)).unwrap().tx, 1);
+    }
+
+    #[test]
+    fn expires_at_the_time_of_a_transaction() {
+        let mut recent = RecentBookings::default();
+        recent.push(1, 1, None, Some(100));
+        recent.executed(Some(100));
+        recent.push(2, 1, None, Some(105));
+        recent.executed(Some(105));
+
+        let window = DisputeWindow::Age(10);
+        assert!(!recent.expires(1, window, Some(110)));
+        assert!(recent.expires(1, window, Some(111)));
+        assert!(!recent.expires(2, window, Some(111)));
+        // the clock has not been moved
+        assert!(recent.expired(window).is_none());
+
+        assert!(!recent.expires(1, DisputeWindow::Transactions(1), Some(1_000)));
+    }
+}
@@ -0,0 +1,346 @@
+use crate::cli::STDIN;
+use flate2::read::MultiGzDecoder;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use zip::ZipArchive;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    None,
+    Gzip,
+    Zip,
+}
+
+impl Compression {
+    /// detects the compression by the file extension, falls back to the leading bytes
+    fn detect(path: &str, magic: &[u8]) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("gz") => Compression::Gzip,
+            Some("zip") => Compression::Zip,
+            _ if magic.starts_with(GZIP_MAGIC) => Compression::Gzip,
+            _ if magic.starts_with(ZIP_MAGIC) => Compression::Zip,
+            _ => Compression::None,
+        }
+    }
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// remembers the first error of the wrapped reader
+///
+/// the csv and json lines readers turn an error into a rejected row, this keeps it to report
+/// the input as unreadable once its rows are processed.
+struct Checked<R> {
+    reader: R,
+    error: Option<io::Error>,
+}
+
+impl<R: Read> Read for Checked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf).inspect_err(|e| {
+            if e.kind() != io::ErrorKind::Interrupted && self.error.is_none() {
+                self.error = Some(io::Error::new(e.kind(), e.to_string()));
+            }
+        })
+    }
+}
+
+impl<R: Read> Checked<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            error: None,
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        self.error.map_or(Ok(()), Err)
+    }
+}
+
+enum Source {
+    Stream(Box<dyn Read>),
+    Archive(ZipArchive<Box<dyn ReadSeek>>),
+}
+
+/// an input file (or stdin) that is transparently decompressed
+///
+/// gzip input is decompressed while it is read. A zip archive yields one csv per member in name
+/// order, as its directory is stored at the end, a zip archive from stdin is buffered in memory.
+pub struct Input {
+    name: String,
+    compression: Compression,
+    source: Source,
+}
+
+impl Input {
+    /// opens the given path, [STDIN] reads from stdin instead
+    pub fn open(path: &str) -> io::Result<Self> {
+        let (compression, reader): (_, Box<dyn ReadSeek>) = match path {
+            STDIN => {
+                let mut stdin = io::stdin().lock();
+                let mut magic = Vec::new();
+                stdin
+                    .by_ref()
+                    .take(ZIP_MAGIC.len() as u64)
+                    .read_to_end(&mut magic)?;
+
+                let compression = Compression::detect(path, &magic);
+                let mut stream = Cursor::new(magic).chain(stdin);
+                if compression != Compression::Zip {
+                    return Ok(Self::stream(path, compression, Box::new(stream)));
+                }
+
+                let mut buffer = Vec::new();
+                stream.read_to_end(&mut buffer)?;
+                (compression, Box::new(Cursor::new(buffer)))
+            }
+            path => {
+                let mut file = File::open(path)?;
+                let mut magic = Vec::new();
+                file.by_ref()
+                    .take(ZIP_MAGIC.len() as u64)
+                    .read_to_end(&mut magic)?;
+                file.seek(SeekFrom::Start(0))?;
+
+                let compression = Compression::detect(path, &magic);
+                if compression != Compression::Zip {
+                    return Ok(Self::stream(path, compression, Box::new(file)));
+                }
+                (compression, Box::new(file))
+            }
+        };
+
+        Ok(Self {
+            name: path.to_string(),
+            compression,
+            source: Source::Archive(ZipArchive::new(reader)?),
+        })
+    }
+
+    fn stream(path: &str, compression: Compression, reader: Box<dyn Read>) -> Self {
+        let reader: Box<dyn Read> = match compression {
+            Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            _ => reader,
+        };
+
+        Self {
+            name: path.to_string(),
+            compression,
+            source: Source::Stream(reader),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compression != Compression::None
+    }
+
+    /// the number of csv files of this input
+    pub fn len(&self) -> usize {
+        match &self.source {
+            Source::Stream(_) => 1,
+            Source::Archive(archive) => Self::members(archive).len(),
+        }
+    }
+
+    /// the names of all files within an archive in name order, directories are skipped
+    fn members(archive: &ZipArchive<Box<dyn ReadSeek>>) -> Vec<String> {
+        let mut members: Vec<String> = archive
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(str::to_string)
+            .collect();
+        members.sort();
+
+        members
+    }
+
+    /// reads every csv of this input one after another
+    ///
+    /// `read` is called with the name of the csv (`<archive>/<member>` for members of an archive)
+    /// and its decompressed content.
+    ///
+    /// # Errors
+    /// if the input could not be read to its end (e.g. a corrupt gzip stream or archive member),
+    /// the csv read so far has already been passed to `read`
+    pub fn read_each<F>(self, mut read: F) -> io::Result<()>
+    where
+        F: FnMut(&str, &mut dyn Read),
+    {
+        match self.source {
+            Source::Stream(reader) => {
+                let mut reader = Checked::new(reader);
+                read(&self.name, &mut reader);
+                reader.finish()
+            }
+            Source::Archive(mut archive) => {
+                for member in Self::members(&archive) {
+                    let in_member =
+                        |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", member, e));
+                    let mut file = Checked::new(
+                        archive
+                            .by_name(&member)
+                            .map_err(io::Error::from)
+                            .map_err(in_member)?,
+                    );
+                    read(&format!("{}/{}", self.name, member), &mut file);
+                    file.finish().map_err(in_member)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::Input;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::env::temp_dir;
+    use std::fs;
+    use std::io::{Cursor, Write};
+    use std::path::{Path, PathBuf};
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    const CSV: &str = "type,client,tx,amount\ndeposit,1,1,1.0\n";
+
+    fn write(name: &str, content: &[u8]) -> PathBuf {
+        let path = temp_dir().join(format!(
+            "payment_engine_input_{}_{}",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, content).unwrap();
+
+        path
+    }
+
+    fn read_all(path: &Path) -> Vec<(String, String)> {
+        let input = Input::open(path.to_str().unwrap()).unwrap();
+        let prefix = format!("{}/", path.display());
+        let mut contents = Vec::new();
+        input
+            .read_each(|name, reader| {
+                let mut content = String::new();
+                reader.read_to_string(&mut content).unwrap();
+                let name = name.strip_prefix(&prefix).unwrap_or("");
+                contents.push((name.to_string(), content));
+            })
+            .unwrap();
+
+        contents
+    }
+
+    fn gzip(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn reads_plain_csv() {
+        let path = write("plain.csv", CSV.as_bytes());
+
+        assert_eq!(read_all(&path), vec![("".to_string(), CSV.to_string())]);
+        assert!(!Input::open(path.to_str().unwrap()).unwrap().is_compressed());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn decompresses_gzip_by_extension_or_content() {
+        for name in ["gzipped.csv.gz", "gzipped.csv"] {
+            let path = write(name, &gzip(CSV.as_bytes()));
+
+            assert_eq!(read_all(&path), vec![("".to_string(), CSV.to_string())]);
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn reads_zip_members_in_name_order() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.add_directory("b/", options).unwrap();
+        for (name, content) in [
+            ("b/2.csv", "second"),
+            ("a.csv", "first"),
+            ("c.csv", "third"),
+        ] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let archive = writer.finish().unwrap().into_inner();
+
+        let path = write("archive", &archive);
+        let input = Input::open(path.to_str().unwrap()).unwrap();
+        assert!(input.is_compressed());
+        assert_eq!(input.len(), 3);
+
+        let names: Vec<(String, String)> = read_all(&path);
+        assert_eq!(
+            names,
+            vec![
+                ("a.csv".to_string(), "first".to_string()),
+                ("b/2.csv".to_string(), "second".to_string()),
+                ("c.csv".to_string(), "third".to_string()),
+            ]
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reports_a_corrupt_input_after_reading_it() {
+        let gzipped = gzip(CSV.as_bytes());
+        let path = write("truncated.csv.gz", &gzipped[..gzipped.len() - 12]);
+        let input = Input::open(path.to_str().unwrap()).unwrap();
+        let mut is_read = false;
+
+        let e = input.read_each(|_, reader| {
+            let mut content = Vec::new();
+            assert!(reader.read_to_end(&mut content).is_err());
+            is_read = true;
+        });
+        assert!(is_read);
+        assert!(e.is_err());
+        fs::remove_file(path).unwrap();
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("a.csv", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(CSV.repeat(10).as_bytes()).unwrap();
+        let mut archive = writer.finish().unwrap().into_inner();
+        // the deflated content starts right after the local header and the member name
+        archive[30 + "a.csv".len()] ^= 0xff;
+
+        let path = write("corrupt.zip", &archive);
+        let input = Input::open(path.to_str().unwrap()).unwrap();
+        let e = input
+            .read_each(|_, reader| {
+                let _ = reader.read_to_end(&mut Vec::new());
+            })
+            .unwrap_err();
+        assert!(e.to_string().starts_with("a.csv: "), "{}", e);
+        fs::remove_file(path).unwrap();
+    }
+}
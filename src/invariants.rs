@@ -0,0 +1,84 @@
+//! checks of the invariants every execution has to keep, shared by the property-based tests and
+//! the fuzz targets
+//!
+//! - a rejected transaction leaves the engine untouched
+//! - the total (available and held) of every balance stays in range of an [Amount]
+//! - a balance holds exactly the amounts of its disputed bookings
+//! - only administrative transactions change a locked client
+//!
+//! [Amount]: crate::Amount
+use crate::client::{Client, ClientId, ExecutionError, State};
+use crate::payment_engine::{PaymentEngine, Transaction};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// executes a transaction and panics if it breaks any invariant
+pub fn execute(engine: &mut PaymentEngine, transaction: Transaction) -> Result<(), ExecutionError> {
+    let before = serde_json::to_value(&*engine).unwrap();
+    let locked = locked_clients(engine);
+    let is_administrative = transaction.is_administrative();
+
+    let result = engine.execute(transaction);
+    if result.is_err() {
+        assert_eq!(
+            serde_json::to_value(&*engine).unwrap(),
+            before,
+            "a rejected transaction changed the engine"
+        );
+    }
+    if !is_administrative {
+        for (id, client) in locked {
+            assert_eq!(
+                without_booked(&engine.client(id).unwrap().unwrap()),
+                client,
+                "a locked client changed"
+            );
+        }
+    }
+    assert_balances(engine);
+
+    result
+}
+
+/// the serialized state of a client without its undisputed bookings, which a dispute window
+/// evicts from locked clients as well
+fn without_booked(client: &Client) -> Value {
+    let mut value = serde_json::to_value(client).unwrap();
+    value["bookings"]
+        .as_object_mut()
+        .unwrap()
+        .retain(|_, booking| booking["state"] != "Booked");
+
+    value
+}
+
+/// the serialized state of all locked clients
+fn locked_clients(engine: &PaymentEngine) -> HashMap<ClientId, Value> {
+    engine
+        .clients()
+        .map(Result::unwrap)
+        .filter(|client| client.is_locked())
+        .map(|client| (client.id(), without_booked(&client)))
+        .collect()
+}
+
+fn assert_balances(engine: &PaymentEngine) {
+    for client in engine.clients().map(Result::unwrap) {
+        for (asset, balance) in client.balances() {
+            assert!(
+                balance.available().checked_add(balance.frozen()).is_some(),
+                "total of client {} overflows",
+                client.id()
+            );
+
+            // only disputed bookings hold funds
+            let disputed: u128 = client
+                .bookings()
+                .filter(|booking| booking.asset() == *asset)
+                .filter(|booking| booking.state() == &State::Disputed)
+                .map(|booking| u128::from(booking.amount()))
+                .sum();
+            assert_eq!(u128::from(balance.frozen()), disputed);
+        }
+    }
+}
@@ -0,0 +1,568 @@
+use crate::asset::Asset;
+use crate::balance::Amount;
+use crate::client::{ClientId, ExecutionError, TransactionId};
+use crate::payment_engine::{Transaction, TransactionType};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::{BufRead, Write};
+
+/// a domain event emitted for every successfully executed transaction
+///
+/// the asset is omitted for the default asset, which keeps single asset journals unchanged.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum Event {
+    DepositBooked {
+        client: ClientId,
+        tx: TransactionId,
+        #[serde(default, skip_serializing_if = "Asset::is_default")]
+        asset: Asset,
+        amount: Amount,
+    },
+    WithdrawalBooked {
+        client: ClientId,
+        tx: TransactionId,
+        #[serde(default, skip_serializing_if = "Asset::is_default")]
+        asset: Asset,
+        amount: Amount,
+    },
+    /// funds moved from `client` to `to_client`
+    TransferBooked {
+        client: ClientId,
+        to_client: ClientId,
+        tx: TransactionId,
+        #[serde(default, skip_serializing_if = "Asset::is_default")]
+        asset: Asset,
+        amount: Amount,
+    },
+    TransactionDisputed {
+        client: ClientId,
+        tx: TransactionId,
+        #[serde(default, skip_serializing_if = "Asset::is_default")]
+        asset: Asset,
+    },
+    DisputeResolved {
+        client: ClientId,
+        tx: TransactionId,
+        #[serde(default, skip_serializing_if = "Asset::is_default")]
+        asset: Asset,
+    },
+    ChargedBack {
+        client: ClientId,
+        tx: TransactionId,
+        #[serde(default, skip_serializing_if = "Asset::is_default")]
+        asset: Asset,
+    },
+    /// a client has been locked after a chargeback
+    ClientLocked {
+        client: ClientId,
+    },
+    /// a client has been locked by an administrator
+    ClientLockedManually {
+        client: ClientId,
+        tx: TransactionId,
+    },
+    ClientUnlocked {
+        client: ClientId,
+        tx: TransactionId,
+    },
+    ClientClosed {
+        client: ClientId,
+        tx: TransactionId,
+    },
+}
+
+impl Event {
+    /// the event describing a successfully executed transaction
+    pub(crate) fn booked(transaction: &Transaction) -> Self {
+        let client = transaction.client();
+        let tx = transaction.id();
+        let asset = transaction.asset();
+
+        match *transaction.transaction_type() {
+            TransactionType::Deposit(amount) => Event::DepositBooked {
+                client,
+                tx,
+                asset,
+                amount,
+            },
+            TransactionType::Withdrawal(amount) => Event::WithdrawalBooked {
+                client,
+                tx,
+                asset,
+                amount,
+            },
+            TransactionType::Transfer { to, amount } => Event::TransferBooked {
+                client,
+                to_client: to,
+                tx,
+                asset,
+                amount,
+            },
+            TransactionType::Dispute => Event::TransactionDisputed { client, tx, asset },
+            TransactionType::Resolve => Event::DisputeResolved { client, tx, asset },
+            TransactionType::Chargeback => Event::ChargedBack { client, tx, asset },
+            TransactionType::Lock => Event::ClientLockedManually { client, tx },
+            TransactionType::Unlock => Event::ClientUnlocked { client, tx },
+            TransactionType::Close => Event::ClientClosed { client, tx },
+        }
+    }
+
+    /// the transaction that caused this event, if any
+    pub(crate) fn transaction(&self) -> Option<Transaction> {
+        let (tx, client, asset, transaction_type) = match *self {
+            Event::DepositBooked {
+                client,
+                tx,
+                asset,
+                amount,
+            } => (tx, client, asset, TransactionType::Deposit(amount)),
+            Event::WithdrawalBooked {
+                client,
+                tx,
+                asset,
+                amount,
+            } => (tx, client, asset, TransactionType::Withdrawal(amount)),
+            Event::TransferBooked {
+                client,
+                to_client,
+                tx,
+                asset,
+                amount,
+            } => (
+                tx,
+                client,
+                asset,
+                TransactionType::Transfer {
+                    to: to_client,
+                    amount,
+                },
+            ),
+            Event::TransactionDisputed { client, tx, asset } => {
+                (tx, client, asset, TransactionType::Dispute)
+            }
+            Event::DisputeResolved { client, tx, asset } => {
+                (tx, client, asset, TransactionType::Resolve)
+            }
+            Event::ChargedBack { client, tx, asset } => {
+                (tx, client, asset, TransactionType::Chargeback)
+            }
+            Event::ClientLockedManually { client, tx } => {
+                (tx, client, Asset::default(), TransactionType::Lock)
+            }
+            Event::ClientUnlocked { client, tx } => {
+                (tx, client, Asset::default(), TransactionType::Unlock)
+            }
+            Event::ClientClosed { client, tx } => {
+                (tx, client, Asset::default(), TransactionType::Close)
+            }
+            Event::ClientLocked { .. } => return None,
+        };
+
+        Some(Transaction::new(tx, client, transaction_type).with_asset(asset))
+    }
+}
+
+/// a sink that receives all events of a [PaymentEngine]
+///
+/// [PaymentEngine]: crate::PaymentEngine
+pub trait Journal {
+    /// appends all events of a single transaction
+    ///
+    /// the transaction is only committed if this succeeds, so an error must be reported here and
+    /// not be deferred to a later call.
+    fn append(&mut self, events: &[Event]) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// a journal writing one JSON object per line
+///
+/// the events of a transaction are serialized into a single buffer, which is written and flushed
+/// at once, so that a failing writer rejects the transaction rather than losing its events later.
+pub struct JsonlJournal<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonlJournal<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> Journal for JsonlJournal<W> {
+    fn append(&mut self, events: &[Event]) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut buffer, event)?;
+            buffer.push(b'\n');
+        }
+
+        self.writer.write_all(&buffer)?;
+        self.writer.flush()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// reads all events from a journal previously written by a [JsonlJournal]
+pub fn read_jsonl<R: BufRead>(reader: R) -> impl Iterator<Item = io::Result<Event>> {
+    reader
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// an event could not be applied, i.e. the journal is inconsistent
+    Execution {
+        index: usize,
+        event: Event,
+        error: ExecutionError,
+    },
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "could not read journal: {}", e),
+            ReplayError::Execution {
+                index,
+                event,
+                error,
+            } => write!(
+                f,
+                "could not apply event {} ({:?}): {}",
+                index, event, error
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(value: io::Error) -> Self {
+        ReplayError::Io(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::Asset;
+    use crate::journal::{read_jsonl, Event, Journal, JsonlJournal, ReplayError};
+    use crate::payment_engine::TransactionType::{
+        Chargeback, Close, Deposit, Dispute, Lock, Resolve, Transfer, Unlock, Withdrawal,
+    };
+    use crate::payment_engine::{PaymentEngine, Transaction};
+    use crate::test_support::snapshot;
+    use crate::ExecutionError::{ClientDoesNotExist, JournalUnavailable};
+    use std::io;
+    use std::io::{BufReader, BufWriter, Cursor, Write};
+    use std::sync::{Arc, Mutex};
+
+    /// collects events in memory so that the test can inspect them
+    #[derive(Clone, Default)]
+    struct SharedJournal(Arc<Mutex<Vec<Event>>>);
+
+    impl Journal for SharedJournal {
+        fn append(&mut self, events: &[Event]) -> io::Result<()> {
+            self.0.lock().unwrap().extend_from_slice(events);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct FailingJournal;
+
+    impl Journal for FailingJournal {
+        fn append(&mut self, _: &[Event]) -> io::Result<()> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// records how many events every single append contains
+    #[derive(Clone, Default)]
+    struct BatchJournal(Arc<Mutex<Vec<usize>>>);
+
+    impl Journal for BatchJournal {
+        fn append(&mut self, events: &[Event]) -> io::Result<()> {
+            self.0.lock().unwrap().push(events.len());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// a writer that accepts everything until it is flushed, like a full disk behind a buffer
+    struct FullDisk;
+
+    impl Write for FullDisk {
+        fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+            Ok(buffer.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::other("disk full"))
+        }
+    }
+
+    fn transactions() -> Vec<Transaction> {
+        vec![
+            Transaction::new(1, 1, Deposit(100)),
+            Transaction::new(2, 1, Withdrawal(30)),
+            Transaction::new(2, 1, Withdrawal(30)),
+            Transaction::new(3, 2, Deposit(50)),
+            Transaction::new(1, 1, Dispute),
+            Transaction::new(2, 1, Dispute),
+            Transaction::new(2, 1, Resolve),
+            Transaction::new(3, 2, Dispute),
+            Transaction::new(3, 2, Chargeback),
+            Transaction::new(4, 2, Deposit(10)),
+            Transaction::new(5, 1, Deposit(20)).with_asset(usd()),
+            Transaction::new(5, 1, Dispute),
+            Transaction::new(5, 1, Dispute).with_asset(usd()),
+            Transaction::new(6, 1, Transfer { to: 3, amount: 10 }),
+            Transaction::new(7, 2, Unlock),
+            Transaction::new(8, 2, Close),
+            Transaction::new(9, 3, Lock),
+        ]
+    }
+
+    fn usd() -> Asset {
+        "USD".parse().unwrap()
+    }
+
+    #[test]
+    fn emits_events_for_successful_transactions() {
+        let journal = SharedJournal::default();
+        let mut engine = PaymentEngine::default().with_journal(journal.clone());
+        for transaction in transactions() {
+            let _ = engine.execute(transaction);
+        }
+
+        assert_eq!(
+            *journal.0.lock().unwrap(),
+            vec![
+                Event::DepositBooked {
+                    client: 1,
+                    tx: 1,
+                    asset: Asset::default(),
+                    amount: 100
+                },
+                Event::WithdrawalBooked {
+                    client: 1,
+                    tx: 2,
+                    asset: Asset::default(),
+                    amount: 30
+                },
+                Event::DepositBooked {
+                    client: 2,
+                    tx: 3,
+                    asset: Asset::default(),
+                    amount: 50
+                },
+                Event::TransactionDisputed {
+                    client: 1,
+                    tx: 2,
+                    asset: Asset::default()
+                },
+                Event::DisputeResolved {
+                    client: 1,
+                    tx: 2,
+                    asset: Asset::default()
+                },
+                Event::TransactionDisputed {
+                    client: 2,
+                    tx: 3,
+                    asset: Asset::default()
+                },
+                Event::ChargedBack {
+                    client: 2,
+                    tx: 3,
+                    asset: Asset::default()
+                },
+                Event::ClientLocked { client: 2 },
+                Event::DepositBooked {
+                    client: 1,
+                    tx: 5,
+                    asset: usd(),
+                    amount: 20
+                },
+                Event::TransactionDisputed {
+                    client: 1,
+                    tx: 5,
+                    asset: usd()
+                },
+                Event::TransferBooked {
+                    client: 1,
+                    to_client: 3,
+                    tx: 6,
+                    asset: Asset::default(),
+                    amount: 10
+                },
+                Event::ClientUnlocked { client: 2, tx: 7 },
+                Event::ClientClosed { client: 2, tx: 8 },
+                Event::ClientLockedManually { client: 3, tx: 9 },
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_rebuilds_identical_state() {
+        let journal = SharedJournal::default();
+        let mut engine = PaymentEngine::default().with_journal(journal.clone());
+        for transaction in transactions() {
+            let _ = engine.execute(transaction);
+        }
+
+        let mut buffer = Vec::new();
+        JsonlJournal::new(&mut buffer)
+            .append(&journal.0.lock().unwrap())
+            .unwrap();
+
+        let events = read_jsonl(BufReader::new(Cursor::new(buffer)));
+        let replayed = PaymentEngine::replay(events).unwrap();
+
+        assert_eq!(
+            snapshot(replayed.clients().map(Result::unwrap)),
+            snapshot(engine.clients().map(Result::unwrap))
+        );
+        assert!(!replayed.has_transaction(&4));
+        assert!(replayed.has_transaction(&3));
+    }
+
+    #[test]
+    fn omits_default_asset() {
+        let event = |asset| Event::DepositBooked {
+            client: 1,
+            tx: 2,
+            asset,
+            amount: 3,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&event(Asset::default())).unwrap(),
+            r#"{"event":"DepositBooked","client":1,"tx":2,"amount":3}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&event(usd())).unwrap(),
+            r#"{"event":"DepositBooked","client":1,"tx":2,"asset":"USD","amount":3}"#
+        );
+    }
+
+    #[test]
+    fn replay_rejects_inconsistent_journal() {
+        let events = vec![Ok(Event::TransactionDisputed {
+            client: 1,
+            tx: 1,
+            asset: Asset::default(),
+        })];
+
+        match PaymentEngine::replay(events) {
+            Err(ReplayError::Execution { index, error, .. }) => {
+                assert_eq!(index, 0);
+                assert_eq!(error, ClientDoesNotExist);
+            }
+            _ => panic!("expected replay to fail"),
+        }
+    }
+
+    #[test]
+    fn admin_log_receives_administrative_events() {
+        let journal = SharedJournal::default();
+        let admin_log = SharedJournal::default();
+        let mut engine = PaymentEngine::default()
+            .with_journal(journal.clone())
+            .with_admin_log(admin_log.clone());
+        for transaction in [
+            Transaction::new(1, 1, Deposit(100)),
+            Transaction::new(7, 1, Lock),
+            Transaction::new(8, 1, Unlock),
+            Transaction::new(9, 1, Close),
+        ] {
+            let _ = engine.execute(transaction);
+        }
+
+        assert_eq!(
+            *admin_log.0.lock().unwrap(),
+            vec![
+                Event::ClientLockedManually { client: 1, tx: 7 },
+                Event::ClientUnlocked { client: 1, tx: 8 },
+            ]
+        );
+        assert_eq!(journal.0.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn failing_journal_does_not_update_client() {
+        let mut engine = PaymentEngine::default().with_journal(FailingJournal);
+
+        assert!(engine
+            .execute(Transaction::new(1, 1, Deposit(100)))
+            .is_err());
+        assert!(engine.client(1).unwrap().is_none());
+        assert!(!engine.has_transaction(&1));
+    }
+
+    #[test]
+    fn appends_all_events_of_a_transaction_at_once() {
+        let journal = BatchJournal::default();
+        let mut engine = PaymentEngine::default().with_journal(journal.clone());
+        for transaction in [
+            Transaction::new(1, 1, Deposit(100)),
+            Transaction::new(1, 1, Dispute),
+            Transaction::new(1, 1, Chargeback),
+        ] {
+            engine.execute(transaction).unwrap();
+        }
+
+        // the chargeback is journaled together with the lock it causes
+        assert_eq!(*journal.0.lock().unwrap(), vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn buffered_write_error_rejects_the_transaction() {
+        let journal = JsonlJournal::new(BufWriter::new(FullDisk));
+        let mut engine = PaymentEngine::default().with_journal(journal);
+
+        assert_eq!(
+            engine.execute(Transaction::new(1, 1, Deposit(100))),
+            Err(JournalUnavailable)
+        );
+        assert!(engine.client(1).unwrap().is_none());
+        assert!(!engine.has_transaction(&1));
+    }
+
+    #[test]
+    fn failing_admin_log_is_not_journaled() {
+        let journal = SharedJournal::default();
+        let mut engine = PaymentEngine::default()
+            .with_journal(journal.clone())
+            .with_admin_log(FailingJournal);
+        engine
+            .execute(Transaction::new(1, 1, Deposit(100)))
+            .unwrap();
+
+        assert_eq!(
+            engine.execute(Transaction::new(7, 1, Lock)),
+            Err(JournalUnavailable)
+        );
+        assert_eq!(journal.0.lock().unwrap().len(), 1);
+        assert!(!engine.client(1).unwrap().unwrap().is_locked());
+    }
+}
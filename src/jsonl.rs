@@ -0,0 +1,155 @@
+use crate::reject::Rejection;
+use crate::row::{Record, Row};
+use booking_engine::{CsvTransactionItem, Transaction};
+use csv::{Position, StringRecord};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read};
+use std::iter;
+
+/// the fields of a json line in the order they are reported in the rejects report
+const FIELDS: [&str; 7] = [
+    "type",
+    "client",
+    "tx",
+    "amount",
+    "asset",
+    "to_client",
+    "timestamp",
+];
+
+pub fn headers() -> StringRecord {
+    StringRecord::from(FIELDS.to_vec())
+}
+
+/// parses a single json line into a transaction
+///
+/// the line is deserialized into the same item as a csv row, so both formats share their
+/// validation (e.g. amounts have to be given as decimal strings).
+fn parse(line: &str) -> Result<Transaction, Rejection> {
+    let item: CsvTransactionItem = serde_json::from_str(line)?;
+
+    Ok(Transaction::try_from(item)?)
+}
+
+/// the original fields of a json line, missing fields are left empty
+pub fn record(text: &str, line: u64) -> Option<StringRecord> {
+    let Ok(Value::Object(object)) = serde_json::from_str(text) else {
+        return None;
+    };
+
+    let mut record: StringRecord = FIELDS
+        .iter()
+        .map(|field| match object.get(*field) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(value)) => value.clone(),
+            Some(value) => value.to_string(),
+        })
+        .collect();
+    let mut position = Position::new();
+    position.set_line(line);
+    record.set_position(Some(position));
+
+    Some(record)
+}
+
+/// reads one transaction per line, blank lines are skipped
+///
+/// an unreadable rest of the input (e.g. a corrupt archive) ends the rows with its error.
+pub fn rows<R: Read>(reader: R) -> impl Iterator<Item = Row> {
+    let mut lines = BufReader::new(reader).lines().zip(1..);
+    let mut is_readable = true;
+
+    iter::from_fn(move || {
+        while is_readable {
+            let (read_line, line) = lines.next()?;
+            match read_line {
+                Ok(text) if text.trim().is_empty() => continue,
+                Ok(text) => {
+                    return Some(Row {
+                        line,
+                        transaction: parse(&text),
+                        record: Some(Record::Json { text, line }),
+                        end: None,
+                    })
+                }
+                Err(e) => {
+                    is_readable = false;
+                    return Some(Row {
+                        line,
+                        record: None,
+                        transaction: Err(Rejection::Io(e)),
+                        end: None,
+                    });
+                }
+            }
+        }
+
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::jsonl::rows;
+    use crate::reject::Rejection;
+    use booking_engine::TransactionType::{Deposit, Dispute, Transfer};
+    use booking_engine::{ParseError, Transaction};
+
+    #[test]
+    fn parses_lines_like_csv_rows() {
+        let input = concat!(
+            r#"{"type":"deposit","client":1,"tx":1,"amount":"1.5"}"#,
+            "\n\n",
+            r#"{"type":"transfer","client":1,"tx":2,"amount":"0.5","to_client":2}"#,
+            "\n",
+            r#"{"type":"dispute","client":1,"tx":1,"amount":null}"#,
+            "\n",
+        );
+        let rows: Vec<(u64, Transaction)> = rows(input.as_bytes())
+            .map(|row| (row.line, row.transaction.unwrap()))
+            .collect();
+
+        assert_eq!(
+            rows,
+            vec![
+                (1, Transaction::new(1, 1, Deposit(15000))),
+                (
+                    3,
+                    Transaction::new(
+                        2,
+                        1,
+                        Transfer {
+                            to: 2,
+                            amount: 5000
+                        }
+                    )
+                ),
+                (4, Transaction::new(1, 1, Dispute)),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        let input = concat!(
+            r#"{"type":"deposit","client":1,"tx":1,"amount":1.5,"timestamp":60}"#,
+            "\n",
+            r#"{"type":"dispute","client":1,"tx":1,"amount":"1.5"}"#,
+            "\n",
+            "not json\n",
+        );
+        let rows: Vec<_> = rows(input.as_bytes()).collect();
+
+        assert!(matches!(rows[0].transaction, Err(Rejection::Json(_))));
+        assert_eq!(
+            *rows[0].record.as_ref().unwrap().fields().unwrap(),
+            vec!["deposit", "1", "1", "1.5", "", "", "60"]
+        );
+        assert!(matches!(
+            rows[1].transaction,
+            Err(Rejection::Parse(ParseError::UnexpectedAmount))
+        ));
+        assert!(matches!(rows[2].transaction, Err(Rejection::Json(_))));
+        assert!(rows[2].record.as_ref().unwrap().fields().is_none());
+    }
+}
@@ -0,0 +1,59 @@
+//! A simple payment engine that executes deposits, withdrawals and disputes on client accounts.
+//!
+//! The engine keeps track of every client's [Balance] per [Asset] and booked transactions. Each
+//! [Transaction] is executed atomically: if any part of it fails, no state is updated.
+//!
+//! # Examples
+//! ```
+//! use booking_engine::{Asset, PaymentEngine, Transaction, TransactionType};
+//!
+//! let mut engine = PaymentEngine::new();
+//! engine
+//!     .execute(Transaction::new(1, 7, TransactionType::Deposit(15_000)))
+//!     .unwrap();
+//! engine
+//!     .execute(Transaction::new(1, 7, TransactionType::Dispute))
+//!     .unwrap();
+//!
+//! let usd: Asset = "USD".parse().unwrap();
+//! engine
+//!     .execute(Transaction::new(2, 7, TransactionType::Deposit(5_000)).with_asset(usd))
+//!     .unwrap();
+//!
+//! let client = engine.into_clients().unwrap().pop().unwrap();
+//! let balance = client.balance(&Asset::default()).unwrap();
+//! assert_eq!(balance.available(), 0);
+//! assert_eq!(balance.frozen(), 15_000);
+//! assert_eq!(client.balance(&usd).unwrap().available(), 5_000);
+//! ```
+mod asset;
+mod balance;
+mod checkpoint;
+mod client;
+mod decimal;
+mod dispute_window;
+#[doc(hidden)]
+#[cfg(any(test, feature = "invariants"))]
+pub mod invariants;
+mod journal;
+mod payment_engine;
+mod record;
+mod sharded_engine;
+mod store;
+#[cfg(test)]
+mod test_support;
+
+pub use asset::{Asset, AssetError, MAX_ASSET_LENGTH};
+pub use balance::{Amount, ArithmeticError, Balance};
+pub use checkpoint::{Checkpoint, InputFile};
+pub use client::{
+    AccountStatus, BookedTransaction, Client, ClientId, Direction, ExecutionError, LockReason,
+    State, TransactionId,
+};
+pub use decimal::{Decimal, DecimalError, PRECISION, SCALE};
+pub use dispute_window::DisputeWindow;
+pub use journal::{read_jsonl, Event, Journal, JsonlJournal, ReplayError};
+pub use payment_engine::{AssetTotals, PaymentEngine, Totals, Transaction, TransactionType};
+pub use record::{ClientFormat, Column, CsvClientItem, CsvTransactionItem, ParseError};
+pub use sharded_engine::{ShardedPaymentEngine, ShardedResult};
+pub use store::{ClientStore, DiskStore, MemoryStore};
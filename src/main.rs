@@ -1,99 +1,613 @@
-use crate::balance::Amount;
-use crate::client::{Client, ClientId, TransactionId};
-use crate::payment_engine::{PaymentEngine, Transaction, TransactionType};
+use crate::cli::{Cli, Format, SortOrder};
+use crate::input::Input;
+use crate::reject::{RejectWriter, Rejection};
+use crate::row::{csv_rows, Row};
+use booking_engine::{
+    read_jsonl, Asset, Checkpoint, Client, ClientFormat, ClientStore, Column, CsvClientItem,
+    DiskStore, InputFile, JsonlJournal, PaymentEngine, ShardedPaymentEngine,
+};
+use clap::error::ErrorKind;
+use clap::CommandFactory;
 use csv::Trim::All;
-use csv::{ReaderBuilder, WriterBuilder};
+use csv::{Position, Reader, ReaderBuilder, WriterBuilder};
 use log::warn;
-use serde::{Deserialize, Serialize};
-use std::env::args;
-use std::fs::File;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::fs::{File, OpenOptions};
 use std::io;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
-mod balance;
-mod client;
-mod payment_engine;
+mod cli;
+mod input;
+mod jsonl;
+mod reject;
+mod row;
 
-const SCALE: f64 = 10000f64;
+/// persists the engine state while processing, only the in-memory state can be saved
+struct Checkpointing<S: ClientStore> {
+    path: PathBuf,
+    input: InputFile,
+    interval: u64,
+    save: fn(&Path, &InputFile, &Position, u64, &PaymentEngine<S>) -> io::Result<()>,
+}
 
-#[derive(Debug, Deserialize)]
-struct CsvTransactionItem {
-    r#type: String,
-    client: ClientId,
-    tx: TransactionId,
-    amount: Option<f64>,
+/// opens all inputs upfront, so that a missing file fails before anything is processed
+///
+/// an input that can not be opened is reported like an invalid argument.
+fn open_all(paths: &[String]) -> Vec<Input> {
+    paths
+        .iter()
+        .map(|path| {
+            Input::open(path).unwrap_or_else(|e| {
+                Cli::command()
+                    .error(
+                        ErrorKind::Io,
+                        format!("could not open input {}: {}", path, e),
+                    )
+                    .exit()
+            })
+        })
+        .collect()
 }
 
-impl Into<Transaction> for CsvTransactionItem {
-    fn into(self) -> Transaction {
-        let transaction_type = match self.r#type.as_str() {
-            "deposit" => {
-                TransactionType::Deposit((self.amount.unwrap() * SCALE).round() as Amount)
-            }
-            "withdrawal" => {
-                TransactionType::Withdrawal((self.amount.unwrap() * SCALE).round() as Amount)
-            }
-            "dispute" => TransactionType::Dispute,
-            "resolve" => TransactionType::Resolve,
-            "chargeback" => TransactionType::Chargeback,
-            _ => panic!("invalid transaction type found"),
-        };
+/// opens the output upfront, so that an invalid path fails before anything is processed
+fn open_output(path: Option<&Path>) -> Box<dyn Write> {
+    match path {
+        Some(path) => Box::new(File::create(path).unwrap_or_else(|e| {
+            Cli::command()
+                .error(
+                    ErrorKind::Io,
+                    format!("could not create output {}: {}", path.display(), e),
+                )
+                .exit()
+        })),
+        None => Box::new(io::stdout().lock()),
+    }
+}
+
+/// reports an input that could not be read to its end, its rows read so far have been processed
+fn exit_input_unreadable(name: &str, e: io::Error) -> ! {
+    Cli::command()
+        .error(
+            ErrorKind::Io,
+            format!("could not read input {}: {}", name, e),
+        )
+        .exit()
+}
+
+fn csv_reader<R: Read>(reader: R) -> Reader<R> {
+    ReaderBuilder::new().trim(All).from_reader(reader)
+}
+
+/// reads all rows of one input and executes them one after another
+///
+/// if configured, the engine state is persisted every `interval` records.
+///
+/// # Errors
+/// if a rejected row can not be written to the rejects report
+fn run<R: Read, S: ClientStore>(
+    mut csv_reader: Reader<R>,
+    rejects: &mut RejectWriter,
+    engine: &mut PaymentEngine<S>,
+    checkpointing: Option<&Checkpointing<S>>,
+) -> io::Result<()> {
+    let headers = csv_reader.headers().cloned().unwrap_or_default();
+    let mut processed: u64 = 0;
+
+    for row in csv_rows(csv_reader, headers) {
+        let result = row
+            .transaction
+            .and_then(|transaction| engine.execute(transaction).map_err(Rejection::from));
+        if let Err(rejection) = result {
+            rejects.reject(row.line, row.record.as_ref(), &rejection)?;
+        }
 
-        Transaction::new(self.tx, self.client, transaction_type)
+        processed += 1;
+        if let Some(checkpointing) = checkpointing {
+            if processed.is_multiple_of(checkpointing.interval) {
+                // the previous checkpoint stays valid, so a failed save only delays the next one
+                let saved = (checkpointing.save)(
+                    &checkpointing.path,
+                    &checkpointing.input,
+                    row.end.as_ref().expect("csv rows know their position"),
+                    rejects.offset()?,
+                    engine,
+                );
+                if let Err(e) = saved {
+                    warn!(
+                        "could not write checkpoint {}: {}",
+                        checkpointing.path.display(),
+                        e
+                    );
+                }
+            }
+        }
     }
+
+    Ok(())
 }
 
-#[derive(Debug, Serialize)]
-struct CsvClientItem {
-    client: ClientId,
-    available: f64,
-    held: f64,
-    total: f64,
-    locked: bool,
+/// reads all rows of a json lines input and executes them one after another
+///
+/// # Errors
+/// if a rejected row can not be written to the rejects report
+fn run_jsonl<R: Read, S: ClientStore>(
+    reader: R,
+    rejects: &mut RejectWriter,
+    engine: &mut PaymentEngine<S>,
+) -> io::Result<()> {
+    for row in jsonl::rows(reader) {
+        let result = row
+            .transaction
+            .and_then(|transaction| engine.execute(transaction).map_err(Rejection::from));
+        if let Err(rejection) = result {
+            rejects.reject(row.line, row.record.as_ref(), &rejection)?;
+        }
+    }
+
+    Ok(())
 }
 
-impl From<Client> for CsvClientItem {
-    fn from(value: Client) -> Self {
-        let available = value.balance().available() as f64;
-        let frozen = value.balance().frozen() as f64;
+/// reads all rows of all inputs and executes them on a [ShardedPaymentEngine]
+///
+/// rejections are only known once all shards are done, so they are reported sorted by input
+/// and line.
+///
+/// # Errors
+/// if a rejected row can not be written to the rejects report
+fn run_sharded(
+    inputs: Vec<Input>,
+    format: Format,
+    rejects: &mut RejectWriter,
+    threads: usize,
+) -> io::Result<Vec<Client>> {
+    let mut engine = ShardedPaymentEngine::new(threads);
+    // name and headers of every csv or json lines file read so far
+    let mut files = Vec::new();
+    let mut rejected = Vec::new();
+
+    for input in inputs {
+        let input_name = input.name().to_string();
+        input
+            .read_each(|name, reader| {
+                let index = files.len();
+                let (headers, rows): (_, Box<dyn Iterator<Item = Row>>) = match format {
+                    Format::Csv => {
+                        let mut csv_reader = csv_reader(reader);
+                        let headers = csv_reader.headers().cloned().unwrap_or_default();
+                        (headers.clone(), Box::new(csv_rows(csv_reader, headers)))
+                    }
+                    Format::Jsonl => (jsonl::headers(), Box::new(jsonl::rows(reader))),
+                };
+
+                for row in rows {
+                    match row.transaction {
+                        Ok(transaction) => {
+                            engine.execute(transaction, (index, row.line, row.record))
+                        }
+                        Err(rejection) => rejected.push((index, row.line, row.record, rejection)),
+                    }
+                }
+                files.push((name.to_string(), headers));
+            })
+            .unwrap_or_else(|e| exit_input_unreadable(&input_name, e));
+    }
+
+    let result = engine.finish();
+    rejected.extend(
+        result
+            .failures
+            .into_iter()
+            .map(|((index, line, record), e)| (index, line, record, Rejection::Execution(e))),
+    );
+    rejected.sort_by_key(|(index, line, _, _)| (*index, *line));
 
-        // this should be safe as the engine makes sure that total is always in range of a u64.
-        let total = available + frozen;
-        Self {
-            client: value.id(),
-            available: available / SCALE,
-            held: frozen / SCALE,
-            total: total / SCALE,
-            locked: value.is_locked(),
+    for ((name, headers), index) in files.into_iter().zip(0..) {
+        rejects.start_input(&name, headers);
+        for (_, line, record, rejection) in rejected.iter().filter(|(i, ..)| *i == index) {
+            rejects.reject(*line, record.as_ref(), rejection)?;
         }
     }
+
+    Ok(result.clients)
+}
+
+/// continues a journal of a replayed state, otherwise a new one is started
+///
+/// the journal is not buffered, as it writes the events of every transaction at once anyway. A
+/// journal that can not be opened is reported like an invalid argument.
+fn open_journal(path: &Path, name: &str, is_replayed: bool) -> JsonlJournal<File> {
+    let journal = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(is_replayed)
+        .truncate(!is_replayed)
+        .open(path)
+        .unwrap_or_else(|e| {
+            Cli::command()
+                .error(
+                    ErrorKind::Io,
+                    format!("could not open {} {}: {}", name, path.display(), e),
+                )
+                .exit()
+        });
+
+    JsonlJournal::new(journal)
+}
+
+/// rebuilds the state of the given journal, a missing or inconsistent journal is reported like
+/// an invalid argument
+fn replay(path: &Path) -> PaymentEngine {
+    let journal = File::open(path).unwrap_or_else(|e| {
+        Cli::command()
+            .error(
+                ErrorKind::Io,
+                format!("could not open journal {}: {}", path.display(), e),
+            )
+            .exit()
+    });
+
+    PaymentEngine::replay(read_jsonl(BufReader::new(journal))).unwrap_or_else(|e| {
+        Cli::command()
+            .error(
+                ErrorKind::InvalidValue,
+                format!("could not replay journal {}: {}", path.display(), e),
+            )
+            .exit()
+    })
+}
+
+/// reports a rejects report that can not be opened or written like an invalid argument
+fn exit_rejects_failed(cli: &Cli, action: &str, e: io::Error) -> ! {
+    let path = cli.rejects.as_deref().unwrap_or(Path::new(""));
+    Cli::command()
+        .error(
+            ErrorKind::Io,
+            format!("could not {} rejects {}: {}", action, path.display(), e),
+        )
+        .exit()
+}
+
+/// creates the rejects report, which only names the csv of a row if there is more than one
+fn new_rejects(cli: &Cli, inputs: &[Input]) -> RejectWriter {
+    let rejects = RejectWriter::new(cli.rejects.as_deref())
+        .unwrap_or_else(|e| exit_rejects_failed(cli, "open", e));
+    match inputs.iter().map(Input::len).sum::<usize>() {
+        0 | 1 => rejects,
+        _ => rejects.with_input_column(),
+    }
 }
 
 fn main() {
-    env_logger::init();
+    let cli = Cli::parse_valid();
 
-    let input_file = args()
-        .nth(1)
-        .expect("input file missing! call: cargo run -- [FILE].csv");
-    let file = File::open(input_file).expect("could not open given input file");
-    let buffered_reader = BufReader::new(file);
-    let mut csv_reader = ReaderBuilder::new().trim(All).from_reader(buffered_reader);
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = cli.log_level {
+        logger.filter_level(level);
+    }
+    logger.init();
 
-    let mut engine = PaymentEngine::default();
+    let inputs = open_all(&cli.inputs);
+    let output = open_output(cli.output.as_deref());
+    if let Some(threads) = cli.threads {
+        let mut rejects = new_rejects(&cli, &inputs);
+        let clients = run_sharded(inputs, cli.input_format, &mut rejects, threads.get())
+            .and_then(|clients| rejects.flush().map(|_| clients))
+            .unwrap_or_else(|e| exit_rejects_failed(&cli, "write", e));
+        let rows = clients
+            .iter()
+            .flat_map(|client| output_rows(client, &cli))
+            .collect();
+        write_clients(rows, &cli, output);
+        return;
+    }
 
-    for deserialized_item in csv_reader.deserialize::<CsvTransactionItem>() {
-        if let Ok(item) = deserialized_item {
-            if let Err(e) = engine.execute(item.into()) {
-                warn!("transaction failed to execute: {:?}", e);
-            }
-        } else {
-            warn!("failed parsing csv line");
+    // a checkpoint stores the position within the raw file, which is only known for plain csv
+    if (cli.checkpoint.is_some() || cli.resume.is_some()) && inputs[0].is_compressed() {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "checkpointing is not supported for compressed input",
+            )
+            .exit();
+    }
+    let checkpointing = cli
+        .checkpoint
+        .as_ref()
+        .or(cli.resume.as_ref())
+        .map(|path| Checkpointing {
+            path: path.clone(),
+            input: InputFile::of(Path::new(&cli.inputs[0]))
+                .expect("could not open given input file"),
+            interval: cli.checkpoint_every,
+            save: Checkpoint::save,
+        });
+    if let Some(checkpointing) = checkpointing.as_ref() {
+        if let Err(e) = Checkpoint::check_writable(&checkpointing.path) {
+            Cli::command()
+                .error(
+                    ErrorKind::Io,
+                    format!(
+                        "could not write checkpoint {}: {}",
+                        checkpointing.path.display(),
+                        e
+                    ),
+                )
+                .exit();
+        }
+    }
+
+    if let Some(resume) = cli.resume.as_ref() {
+        let checkpoint = Checkpoint::load(resume).unwrap_or_else(|e| {
+            // serde errors are turned into invalid data, everything else could not be read
+            let kind = match e.kind() {
+                io::ErrorKind::InvalidData => ErrorKind::InvalidValue,
+                _ => ErrorKind::Io,
+            };
+            Cli::command()
+                .error(
+                    kind,
+                    format!("could not read checkpoint {}: {}", resume.display(), e),
+                )
+                .exit()
+        });
+        let input = &checkpointing.as_ref().unwrap().input;
+        if checkpoint.input() != input {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!(
+                        "the checkpoint belongs to {}, not to {}",
+                        checkpoint.input(),
+                        input
+                    ),
+                )
+                .exit();
+        }
+        let file = File::open(&cli.inputs[0]).expect("could not open given input file");
+        let mut csv_reader = csv_reader(file);
+        // the headers are read before seeking past them
+        let headers = csv_reader.headers().cloned().unwrap_or_default();
+        csv_reader
+            .seek(checkpoint.position())
+            .expect("could not seek input file to the checkpoint");
+
+        let mut rejects = RejectWriter::resume(cli.rejects.as_deref(), checkpoint.rejects_offset())
+            .unwrap_or_else(|e| exit_rejects_failed(&cli, "open", e));
+        rejects.start_input(&cli.inputs[0], headers);
+        let mut engine = checkpoint.into_engine();
+        if let Some(dispute_window) = cli.dispute_window() {
+            engine = engine.with_dispute_window(dispute_window);
         }
+        run(
+            csv_reader,
+            &mut rejects,
+            &mut engine,
+            checkpointing.as_ref(),
+        )
+        .and_then(|_| rejects.flush())
+        .unwrap_or_else(|e| exit_rejects_failed(&cli, "write", e));
+        write_clients(client_rows(&mut engine, &cli), &cli, output);
+        return;
     }
 
-    let mut writer = WriterBuilder::new().from_writer(io::stdout());
-    for x in engine.into_clients() {
-        let item: CsvClientItem = x.into();
-        writer.serialize(item).unwrap();
+    if let Some(path) = cli.store.as_ref() {
+        let store = DiskStore::create(path, cli.store_cache).unwrap_or_else(|e| {
+            Cli::command()
+                .error(
+                    ErrorKind::Io,
+                    format!("could not create store {}: {}", path.display(), e),
+                )
+                .exit()
+        });
+        process(PaymentEngine::with_store(store), inputs, &cli, output, None);
+        return;
+    }
+
+    // rebuild the state from a previous journal or start from scratch
+    let engine = match cli.replay.as_ref() {
+        Some(path) => replay(path),
+        None => PaymentEngine::default(),
+    };
+    process(engine, inputs, &cli, output, checkpointing.as_ref());
+}
+
+/// executes all inputs on the given engine and writes the resulting clients
+fn process<S: ClientStore>(
+    mut engine: PaymentEngine<S>,
+    inputs: Vec<Input>,
+    cli: &Cli,
+    output: Box<dyn Write>,
+    checkpointing: Option<&Checkpointing<S>>,
+) {
+    if let Some(dispute_window) = cli.dispute_window() {
+        engine = engine.with_dispute_window(dispute_window);
+    }
+    let is_replayed = cli.replay.is_some();
+    if let Some(path) = cli.journal.as_ref() {
+        engine = engine.with_journal(open_journal(path, "journal", is_replayed));
+    }
+    if let Some(path) = cli.admin_log.as_ref() {
+        engine = engine.with_admin_log(open_journal(path, "admin log", is_replayed));
+    }
+
+    // without any input, the replayed state is the final state
+    let mut rejects = new_rejects(cli, &inputs);
+    for input in inputs {
+        let input_name = input.name().to_string();
+        input
+            .read_each(|name, reader| {
+                let result = match cli.input_format {
+                    Format::Csv => {
+                        let mut csv_reader = csv_reader(reader);
+                        let headers = csv_reader.headers().cloned().unwrap_or_default();
+                        rejects.start_input(name, headers);
+                        run(csv_reader, &mut rejects, &mut engine, checkpointing)
+                    }
+                    Format::Jsonl => {
+                        rejects.start_input(name, jsonl::headers());
+                        run_jsonl(reader, &mut rejects, &mut engine)
+                    }
+                };
+                result.unwrap_or_else(|e| exit_rejects_failed(cli, "write", e));
+            })
+            .unwrap_or_else(|e| {
+                // the rows read so far are kept in the report
+                let _ = rejects.flush();
+                exit_input_unreadable(&input_name, e)
+            });
+    }
+    rejects
+        .flush()
+        .unwrap_or_else(|e| exit_rejects_failed(cli, "write", e));
+    engine.flush_journal().unwrap_or_else(|e| {
+        Cli::command()
+            .error(ErrorKind::Io, format!("could not write journal: {}", e))
+            .exit()
+    });
+
+    write_clients(client_rows(&mut engine, cli), cli, output);
+}
+
+/// a single output row of a client and asset together with the keys it is sorted by
+struct OutputRow {
+    asset: Asset,
+    total: u128,
+    locked: bool,
+    item: CsvClientItem,
+}
+
+/// the output rows of a client, one per asset
+///
+/// all rows carry their asset, it is dropped later on if the asset column is not written.
+fn output_rows(client: &Client, cli: &Cli) -> Vec<OutputRow> {
+    let format = ClientFormat {
+        with_asset: true,
+        fixed_decimals: cli.fixed_decimals,
+    };
+
+    client
+        .balances()
+        .zip(CsvClientItem::rows(client, format))
+        .map(|((asset, balance), item)| OutputRow {
+            asset: *asset,
+            total: u128::from(balance.available()) + u128::from(balance.frozen()),
+            locked: client.is_locked(),
+            item,
+        })
+        .collect()
+}
+
+/// the output rows of all clients ordered by client id and asset
+///
+/// only the rows are collected, so that the booking history of a disk store is never loaded
+/// into memory all at once.
+fn client_rows<S: ClientStore>(engine: &mut PaymentEngine<S>, cli: &Cli) -> Vec<OutputRow> {
+    let mut rows = Vec::new();
+    engine
+        .for_each_client(|client| rows.extend(output_rows(client, cli)))
+        .expect("could not read clients from the store");
+
+    rows
+}
+
+/// the columns to write, unless picked the asset column is only added if any client holds a
+/// balance in another than the default asset and the status columns only if requested
+fn output_columns(rows: &[OutputRow], cli: &Cli) -> Vec<Column> {
+    if !cli.columns.is_empty() {
+        return cli.columns.clone();
+    }
+
+    let with_asset = rows.iter().any(|row| !row.asset.is_default());
+    Column::ALL
+        .into_iter()
+        .filter(|column| with_asset || *column != Column::Asset)
+        .filter(|column| cli.status || !column.is_status())
+        .collect()
+}
+
+/// the fields of an output row, serialized into a json object keyed in the order of the columns
+struct JsonRow<'a>(&'a [(&'static str, Value)]);
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(name, value)| (name, value)))
+    }
+}
+
+/// writes one row (or json line) per client and asset to the given output
+///
+/// a closed pipe (e.g. `| head`) ends the process quietly, any other error is reported.
+fn write_clients(rows: Vec<OutputRow>, cli: &Cli, output: Box<dyn Write>) {
+    match write_rows(rows, cli, output) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+        Err(e) => {
+            let name = match cli.output.as_deref() {
+                Some(path) => path.display().to_string(),
+                None => "stdout".to_string(),
+            };
+            Cli::command()
+                .error(
+                    ErrorKind::Io,
+                    format!("could not write output {}: {}", name, e),
+                )
+                .exit()
+        }
+    }
+}
+
+/// the io error of a csv writer, so that a closed pipe can be told apart
+fn into_io_error(e: csv::Error) -> io::Error {
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => e,
+        kind => io::Error::other(format!("{:?}", kind)),
+    }
+}
+
+fn write_rows(mut rows: Vec<OutputRow>, cli: &Cli, output: Box<dyn Write>) -> io::Result<()> {
+    let columns = output_columns(&rows, cli);
+
+    // the rows are ordered by client id and asset, the stable sorts keep that for ties
+    match cli.sort {
+        SortOrder::Client => {}
+        SortOrder::Total => rows.sort_by_key(|row| Reverse(row.total)),
+        SortOrder::Locked => rows.sort_by_key(|row| !row.locked),
+    }
+
+    let rows = rows.into_iter().map(|row| {
+        columns
+            .iter()
+            .map(|column| (column.name(), row.item.field(*column)))
+            .collect::<Vec<_>>()
+    });
+
+    match cli.output_format {
+        Format::Csv => {
+            let mut writer = WriterBuilder::new().from_writer(output);
+            writer
+                .write_record(columns.iter().map(Column::name))
+                .map_err(into_io_error)?;
+            for row in rows {
+                let fields = row.into_iter().map(|(_, value)| match value {
+                    Value::Null => String::new(),
+                    Value::String(value) => value,
+                    value => value.to_string(),
+                });
+                writer.write_record(fields).map_err(into_io_error)?;
+            }
+            writer.flush()
+        }
+        Format::Jsonl => {
+            let mut writer = BufWriter::new(output);
+            for row in rows {
+                serde_json::to_writer(&mut writer, &JsonRow(&row))?;
+                writeln!(writer)?;
+            }
+            writer.flush()
+        }
     }
 }
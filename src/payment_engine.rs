@@ -1,10 +1,25 @@
-use crate::balance::{Amount, ArithmeticError};
-use crate::client::ExecutionError::{Arithmetic, ClientDoesNotExist, ClientLocked};
-use crate::client::{BookedDeposit, Client, ClientId, ExecutionError, TransactionId};
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-
-pub type ClientMap = HashMap<ClientId, Client>;
+use crate::asset::Asset;
+use crate::balance::{Amount, ArithmeticError, Balance};
+use crate::client::ExecutionError::{
+    Arithmetic, ClientClosed, ClientDoesNotExist, DisputeWindowExpired, DuplicateTransaction,
+    InvalidBooking, InvalidStatus, JournalUnavailable, NonZeroBalance, SelfTransfer,
+    StoreUnavailable,
+};
+use crate::client::{
+    AccountStatus, BookedTransaction, Client, ClientId, Direction, ExecutionError, LockReason,
+    TransactionId,
+};
+use crate::dispute_window::{DisputeWindow, RecentBookings};
+use crate::journal::{Event, Journal, ReplayError};
+use crate::store::{ClientStore, MemoryStore};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+/// all executed deposits and withdrawals together with the client they have been booked for
+type TransactionRegistry = HashMap<TransactionId, ClientId>;
 
 impl From<ArithmeticError> for ExecutionError {
     fn from(value: ArithmeticError) -> Self {
@@ -12,146 +27,778 @@ impl From<ArithmeticError> for ExecutionError {
     }
 }
 
-#[derive(Default)]
-pub struct PaymentEngine {
-    clients: ClientMap,
+/// executes transactions on the clients of a [ClientStore], all clients are kept in memory by
+/// default
+#[derive(Serialize, Deserialize)]
+pub struct PaymentEngine<S: ClientStore = MemoryStore> {
+    clients: S,
+    transactions: TransactionRegistry,
+    /// the receiving client of every transfer executed within a dispute window, which holds a
+    /// booking of it as well
+    #[serde(default)]
+    transfers: TransactionRegistry,
+    /// bookings that are evicted once they fall outside of the dispute window
+    #[serde(default)]
+    recent: RecentBookings,
+    #[serde(skip)]
+    dispute_window: Option<DisputeWindow>,
+    #[serde(skip)]
+    journal: Option<Box<dyn Journal + Send>>,
+    #[serde(skip)]
+    admin_log: Option<Box<dyn Journal + Send>>,
 }
 
-impl PaymentEngine {
+impl Default for PaymentEngine {
+    fn default() -> Self {
+        Self::with_store(MemoryStore::default())
+    }
+}
+
+impl<S: ClientStore> PaymentEngine<S> {
+    /// creates an engine that keeps its clients in the given store
+    pub fn with_store(clients: S) -> Self {
+        Self {
+            clients,
+            transactions: TransactionRegistry::default(),
+            transfers: TransactionRegistry::default(),
+            recent: RecentBookings::default(),
+            dispute_window: None,
+            journal: None,
+            admin_log: None,
+        }
+    }
+
+    /// evicts bookings that are still undisputed once they fall outside of the given window
+    ///
+    /// only bookings executed after the window has been set are evicted.
+    pub fn with_dispute_window(mut self, dispute_window: DisputeWindow) -> Self {
+        self.dispute_window = Some(dispute_window);
+        self
+    }
+
+    /// emits an [Event] to the given journal for every successfully executed transaction
+    pub fn with_journal<J: Journal + Send + 'static>(mut self, journal: J) -> Self {
+        self.journal = Some(Box::new(journal));
+        self
+    }
+
+    /// emits an [Event] to the given log for every successfully executed administrative
+    /// transaction, in addition to the journal
+    pub fn with_admin_log<J: Journal + Send + 'static>(mut self, admin_log: J) -> Self {
+        self.admin_log = Some(Box::new(admin_log));
+        self
+    }
+
+    /// flushes all events written to the journal and the admin log so far
+    pub fn flush_journal(&mut self) -> io::Result<()> {
+        for journal in [self.journal.as_mut(), self.admin_log.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            journal.flush()?;
+        }
+
+        Ok(())
+    }
+
     /// Executes a given Transaction and updates the client state
     ///
     /// executes a transaction and - if successful - updates the internal client state
     /// if any error occurs during execution the client is not updated.
+    ///
+    /// Instead of copying the whole client, only the parts a transaction can touch (its balance
+    /// and at most one booking) are staged and committed together once all checks passed.
+    /// Transfers and their disputes touch the counterparty as well, both sides are staged and
+    /// committed together so that either both clients are updated or neither is.
+    ///
+    /// Administrative transactions only change the account status and are accepted for locked
+    /// clients as well.
+    ///
+    /// If a dispute window is set, only a successfully executed transaction moves its clock and
+    /// evicts all bookings that fell outside of it afterwards.
     pub fn execute(&mut self, transaction: Transaction) -> Result<(), ExecutionError> {
+        // evictions left over by a failing store are retried first
+        self.expire_bookings()?;
+
+        let timestamp = transaction.timestamp;
+        let result = if transaction.is_administrative() {
+            self.administer(&transaction)
+        } else {
+            self.book(transaction)
+        };
+
+        if result.is_ok() {
+            self.recent.executed(timestamp);
+            // the transaction has been committed regardless, the eviction is retried
+            let _ = self.expire_bookings();
+        }
+
+        result
+    }
+
+    /// evicts all undisputed bookings that fell outside of the dispute window
+    fn expire_bookings(&mut self) -> Result<(), ExecutionError> {
+        let Some(window) = self.dispute_window else {
+            return Ok(());
+        };
+
+        while let Some(booking) = self.recent.expired(window) {
+            for id in [Some(booking.client), booking.counterparty]
+                .into_iter()
+                .flatten()
+            {
+                if let Some(client) = load(&mut self.clients, id)? {
+                    client.expire_booking(booking.tx);
+                }
+            }
+            self.recent.pop();
+        }
+
+        Ok(())
+    }
+
+    /// executes a deposit, withdrawal, transfer or the dispute of one of them
+    fn book(&mut self, transaction: Transaction) -> Result<(), ExecutionError> {
+        // deposits, withdrawals and transfers introduce a new, globally unique transaction id
+        let is_new_transaction = transaction.is_new_transaction();
+        if is_new_transaction && self.transactions.contains_key(&transaction.id) {
+            return Err(DuplicateTransaction);
+        }
+
+        // a transfer to the sending client would stage the same balance twice
+        let is_self_transfer = matches!(
+            transaction.transaction_type,
+            TransactionType::Transfer { to, .. } if to == transaction.client
+        );
+        if is_self_transfer {
+            return Err(SelfTransfer);
+        }
+
+        // a booking of this client that has been executed but is gone has been evicted
+        let is_evicted = self.dispute_window.is_some()
+            && [&self.transactions, &self.transfers]
+                .iter()
+                .any(|registry| registry.get(&transaction.id) == Some(&transaction.client));
+
         // try retrieve a previously stored client
-        let mut client = match self.clients.entry(transaction.client) {
-            // create a copy of it so that we do not mutate the state immediately
-            Entry::Occupied(e) => Ok(e.get().clone()),
+        let staged = match load(&mut self.clients, transaction.client)? {
+            // do not proceed if the client has been previously locked or closed, otherwise
+            // stage a copy of its balance so that we do not mutate the state immediately
+            Some(client) => client
+                .ensure_active()
+                .and_then(|_| Staged::stage(Some(client), &transaction))
+                .map_err(|error| match error {
+                    InvalidBooking
+                        if is_evicted && client.get_booking(&transaction.id).is_err() =>
+                    {
+                        DisputeWindowExpired
+                    }
+                    error => error,
+                }),
             // if the client does not exist
-            Entry::Vacant(_) => match transaction.transaction_type {
-                // ...and the transaction is a deposit, create a new one
-                TransactionType::Deposit(_) => Ok(Client::new(transaction.client)),
+            None => match transaction.transaction_type {
+                // ...and the transaction is a deposit, it will be created on commit
+                TransactionType::Deposit(_) => Staged::stage(None, &transaction),
                 // ... or return an error for all other tx types
                 _ => Err(ClientDoesNotExist),
             },
         }?;
 
-        // do not proceed if the client has been previously locked
-        if client.is_locked() {
-            return Err(ClientLocked);
+        // the clock has not been moved by the dispute yet, but it must not refer to a booking
+        // that has fallen outside of the window at its own time
+        let is_expiring = matches!(transaction.transaction_type, TransactionType::Dispute)
+            && self.dispute_window.is_some_and(|window| {
+                self.recent
+                    .expires(transaction.id, window, transaction.timestamp)
+            });
+        if is_expiring {
+            return Err(DisputeWindowExpired);
         }
 
-        match transaction.transaction_type {
-            TransactionType::Deposit(amount) => self.deposit(&mut client, amount, transaction.id),
-            TransactionType::Withdrawal(amount) => self.withdraw(&mut client, amount),
-            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
-                // try and get previously booked deposit
-                let mut booking = client.get_booking_mut(&transaction.id)?.clone();
+        // a transfer (or a dispute of one) also books on the counterparty
+        let counterparty = match staged.counterparty() {
+            Some(id) => {
+                // the receiver of a transfer is created on commit just like for a deposit
+                let client = load(&mut self.clients, id)?.map(|client| &*client);
+
+                // neither side of a transfer may be locked or closed
+                client
+                    .map_or(Ok(()), Client::ensure_active)
+                    .and_then(|_| Staged::stage_counterparty(client, &transaction))
+                    .map(|staged| Some((id, staged)))
+            }
+            None => Ok(None),
+        }?;
 
-                match transaction.transaction_type {
-                    TransactionType::Dispute => {
-                        // check if disputable
-                        booking.dispute()?;
+        // load both clients at once before journaling, so that committing requires no i/o
+        let counterparty_id = counterparty.as_ref().map(|(id, _)| *id);
+        let (client, other) = load_pair(&mut self.clients, transaction.client, counterparty_id)?;
 
-                        // freeze amount
-                        client.get_balance_mut().freeze(booking.amount())?;
-                    }
-                    TransactionType::Resolve => {
-                        // check if resolvable
-                        booking.resolve()?;
+        // journal all events of the execution at once before committing it, so that a failing
+        // journal leaves no trace
+        if let Some(journal) = self.journal.as_mut() {
+            let booked = Event::booked(&transaction);
+            let locked = staged.lock.then_some(Event::ClientLocked {
+                client: transaction.client,
+            });
+            let events: Vec<Event> = [Some(booked), locked].into_iter().flatten().collect();
 
-                        // unfreeze amount
-                        client.get_balance_mut().unfreeze(booking.amount())?;
-                    }
-                    TransactionType::Chargeback => {
-                        // check if chargeback is possible
-                        booking.chargeback()?;
+            append(journal.as_mut(), &events)?;
+        }
 
-                        // chargeback amount
-                        client.get_balance_mut().chargeback(booking.amount())?;
+        // commit staged changes, new clients are added once the loaded ones are committed
+        let created = [
+            commit(transaction.client, client, staged),
+            counterparty.and_then(|(id, staged)| commit(id, other, staged)),
+        ];
+        for client in created.into_iter().flatten() {
+            self.clients.put(client);
+        }
 
-                        // clients are locked if they chargeback
-                        client.lock();
-                    }
-                    _ => unreachable!(
-                        "this path is only reachable through Dispute, Resolve or Chargeback"
-                    ),
+        // register the transaction id only after it has been successfully executed
+        if is_new_transaction {
+            self.transactions.insert(transaction.id, transaction.client);
+            if self.dispute_window.is_some() {
+                if let Some(to) = counterparty_id {
+                    self.transfers.insert(transaction.id, to);
                 }
+                self.recent.push(
+                    transaction.id,
+                    transaction.client,
+                    counterparty_id,
+                    transaction.timestamp,
+                );
+            }
+        }
+
+        Ok(())
+    }
 
-                // update booking with cloned value
-                client.add_or_update_booking(booking);
+    /// applies an administrative transaction to the account status of an existing client
+    fn administer(&mut self, transaction: &Transaction) -> Result<(), ExecutionError> {
+        let client = load(&mut self.clients, transaction.client)?.ok_or(ClientDoesNotExist)?;
 
-                Ok(())
+        let status = match (&transaction.transaction_type, client.status()) {
+            // a closed account cannot be reopened
+            (_, AccountStatus::Closed) => Err(ClientClosed),
+            (TransactionType::Lock, AccountStatus::Active) => {
+                Ok(AccountStatus::Locked(LockReason::Manual))
             }
+            (TransactionType::Unlock, AccountStatus::Locked(_)) => Ok(AccountStatus::Active),
+            (TransactionType::Close, _) if client.has_zero_balance() => Ok(AccountStatus::Closed),
+            (TransactionType::Close, _) => Err(NonZeroBalance),
+            _ => Err(InvalidStatus),
         }?;
 
-        // update client
-        self.clients.insert(transaction.client, client);
+        // the journal is written last, as only it is required to rebuild the state: a failing
+        // admin log rejects the transaction before it is journaled, a failing journal leaves an
+        // attempt in the admin log but no trace in the state
+        let event = [Event::booked(transaction)];
+        for journal in [self.admin_log.as_mut(), self.journal.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            append(journal.as_mut(), &event)?;
+        }
+
+        client.set_status(status);
 
         Ok(())
     }
 
+    /// checks whether a deposit, withdrawal or transfer with the given id has been executed
+    pub fn has_transaction(&self, tx: &TransactionId) -> bool {
+        self.transactions.contains_key(tx)
+    }
+
+    /// returns a client if it exists
+    ///
+    /// # Errors
+    /// if the store cannot read the client
+    pub fn client(&self, id: ClientId) -> io::Result<Option<Cow<'_, Client>>> {
+        self.clients.client(id)
+    }
+
+    /// iterates over all clients in arbitrary order
+    pub fn clients(&self) -> impl Iterator<Item = io::Result<Cow<'_, Client>>> {
+        self.clients.clients()
+    }
+
+    /// looks up a booked deposit or withdrawal by its id
+    ///
+    /// returns the client it has been booked for together with the booking (and its [State]).
+    ///
+    /// # Errors
+    /// if the store cannot read the client
+    ///
+    /// [State]: crate::State
+    pub fn booking(&self, tx: TransactionId) -> io::Result<Option<(ClientId, BookedTransaction)>> {
+        let Some(client) = self.transactions.get(&tx) else {
+            return Ok(None);
+        };
+        let booking = self
+            .clients
+            .client(*client)?
+            .and_then(|c| c.get_booking(&tx).ok().cloned());
+
+        Ok(booking.map(|booking| (*client, booking)))
+    }
+
+    /// sums up the balances of all clients per asset
+    ///
+    /// # Errors
+    /// if the store cannot read a client
+    pub fn totals(&self) -> io::Result<Totals> {
+        let mut totals = Totals::default();
+
+        for client in self.clients.clients() {
+            let client = client?;
+            for (asset, balance) in client.balances() {
+                let available = balance.available() as u128;
+                let held = balance.frozen() as u128;
+
+                let asset_totals = totals.assets.entry(*asset).or_default();
+                asset_totals.available += available;
+                asset_totals.held += held;
+                asset_totals.total += available + held;
+            }
+
+            totals.locked += usize::from(client.is_locked());
+            totals.closed += usize::from(client.status() == AccountStatus::Closed);
+        }
+
+        Ok(totals)
+    }
+
+    /// calls `f` for every client ordered by id
+    pub fn for_each_client<F: FnMut(&Client)>(&mut self, f: F) -> io::Result<()> {
+        self.clients.for_each(f)
+    }
+
+    /// consumes the engine into its store
+    pub fn into_store(self) -> S {
+        self.clients
+    }
+
     /// consumes the engine into client vec
     ///
-    /// exposes all clients as a vector, so that we can finalize the payment process
-    pub fn into_clients(self) -> Vec<Client> {
-        self.clients.into_values().map(|kv| kv).collect()
+    /// exposes all clients as a vector ordered by their id, so that we can finalize the payment
+    /// process with a reproducible output
+    ///
+    /// # Errors
+    /// if the store cannot read a client
+    pub fn into_clients(self) -> io::Result<Vec<Client>> {
+        self.clients.into_clients()
+    }
+}
+
+impl PaymentEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// rebuilds an engine purely from the events of a journal
+    ///
+    /// # Errors
+    /// - [ReplayError::Io] if an event could not be read
+    /// - [ReplayError::Execution] if an event cannot be applied to the rebuilt state
+    pub fn replay<I>(journal: I) -> Result<Self, ReplayError>
+    where
+        I: IntoIterator<Item = io::Result<Event>>,
+    {
+        let mut engine = Self::default();
+
+        for (index, event) in journal.into_iter().enumerate() {
+            let event = event?;
+            let result = match (event.transaction(), &event) {
+                (Some(transaction), _) => engine.execute(transaction),
+                (None, Event::ClientLocked { client }) => engine
+                    .clients
+                    .client_mut(*client)
+                    .map(|client| client.lock(LockReason::Chargeback))
+                    .ok_or(ClientDoesNotExist),
+                (None, _) => unreachable!("every other event is caused by a transaction"),
+            };
+
+            result.map_err(|error| ReplayError::Execution {
+                index,
+                event,
+                error,
+            })?;
+        }
+
+        Ok(engine)
+    }
+
+    /// removes a client, e.g. to execute a transfer on the engine owning its counterparty
+    pub(crate) fn take_client(&mut self, id: ClientId) -> Option<Client> {
+        self.clients.remove(id)
+    }
+
+    /// adds a client previously removed by [PaymentEngine::take_client]
+    pub(crate) fn insert_client(&mut self, client: Client) {
+        self.clients.insert(client);
+    }
+}
+
+/// aggregated balances across all clients
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Totals {
+    /// the summed up balances of every asset any client holds
+    pub assets: BTreeMap<Asset, AssetTotals>,
+    /// number of locked clients
+    pub locked: usize,
+    /// number of closed clients
+    pub closed: usize,
+}
+
+/// the summed up balances of a single asset
+///
+/// amounts are summed up as `u128`, as the sum of many [Amount]s can exceed its range.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AssetTotals {
+    pub available: u128,
+    pub held: u128,
+    pub total: u128,
+}
+
+/// commits staged changes to a loaded client, or returns the client it creates
+fn commit(id: ClientId, loaded: Option<&mut Client>, staged: Staged) -> Option<Client> {
+    match loaded {
+        Some(client) => {
+            staged.commit(client);
+            None
+        }
+        None => {
+            let mut client = Client::new(id);
+            staged.commit(&mut client);
+            Some(client)
+        }
+    }
+}
+
+/// loads a client from the store, a failing store rejects the transaction
+fn load<S: ClientStore>(
+    clients: &mut S,
+    id: ClientId,
+) -> Result<Option<&mut Client>, ExecutionError> {
+    clients.get(id).map_err(|e| {
+        error!("could not load client {} from the store: {}", id, e);
+        StoreUnavailable
+    })
+}
+
+/// the loaded clients of a transaction and its counterparty
+type LoadedPair<'a> = (Option<&'a mut Client>, Option<&'a mut Client>);
+
+/// loads a client together with its counterparty, see [ClientStore::get_pair]
+fn load_pair<S: ClientStore>(
+    clients: &mut S,
+    id: ClientId,
+    other: Option<ClientId>,
+) -> Result<LoadedPair<'_>, ExecutionError> {
+    clients.get_pair(id, other).map_err(|e| {
+        error!("could not load client {} from the store: {}", id, e);
+        StoreUnavailable
+    })
+}
+
+/// writes the events of a transaction to a journal, a failing journal rejects the transaction
+fn append(journal: &mut (dyn Journal + Send), events: &[Event]) -> Result<(), ExecutionError> {
+    journal.append(events).map_err(|e| {
+        error!("could not write event to journal: {}", e);
+        JournalUnavailable
+    })
+}
+
+/// the changes a single transaction applies to a client
+///
+/// a transaction only ever touches the balance of its asset and at most one booking per client,
+/// so staging those keeps execution O(1) independent of the size of the booking history.
+struct Staged {
+    asset: Asset,
+    balance: Balance,
+    booking: Option<BookedTransaction>,
+    lock: bool,
+}
+
+impl Staged {
+    fn new(client: Option<&Client>, asset: Asset) -> Self {
+        Self {
+            asset,
+            balance: client
+                .and_then(|c| c.balance(&asset))
+                .cloned()
+                .unwrap_or_default(),
+            booking: None,
+            lock: false,
+        }
+    }
+
+    /// applies a transaction to a copy of the touched client state
+    fn stage(client: Option<&Client>, transaction: &Transaction) -> Result<Self, ExecutionError> {
+        let mut staged = Self::new(client, transaction.asset);
+
+        match transaction.transaction_type {
+            TransactionType::Deposit(amount) => staged.deposit(amount, transaction.id, None)?,
+            TransactionType::Withdrawal(amount) => staged.withdraw(amount, transaction.id, None)?,
+            // the sending side of a transfer is booked like a withdrawal
+            TransactionType::Transfer { to, amount } => {
+                staged.withdraw(amount, transaction.id, Some(to))?
+            }
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+                staged.settle(client.ok_or(ClientDoesNotExist)?, transaction)?;
+
+                // clients are locked if they chargeback
+                staged.lock = transaction.transaction_type == TransactionType::Chargeback;
+            }
+            TransactionType::Lock | TransactionType::Unlock | TransactionType::Close => {
+                unreachable!("administrative transactions are not staged")
+            }
+        }
+
+        Ok(staged)
+    }
+
+    /// applies a transaction to a copy of the counterparty's state
+    ///
+    /// the counterparty is never locked, as it has not initiated the chargeback.
+    fn stage_counterparty(
+        client: Option<&Client>,
+        transaction: &Transaction,
+    ) -> Result<Self, ExecutionError> {
+        let mut staged = Self::new(client, transaction.asset);
+
+        match transaction.transaction_type {
+            // the receiving side of a transfer is booked like a deposit
+            TransactionType::Transfer { amount, .. } => {
+                staged.deposit(amount, transaction.id, Some(transaction.client))?
+            }
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+                staged.settle(client.ok_or(ClientDoesNotExist)?, transaction)?
+            }
+            TransactionType::Deposit(_)
+            | TransactionType::Withdrawal(_)
+            | TransactionType::Lock
+            | TransactionType::Unlock
+            | TransactionType::Close => unreachable!("only transfers have a counterparty"),
+        }
+
+        Ok(staged)
+    }
+
+    /// the other client a staged booking has been transferred from or to
+    fn counterparty(&self) -> Option<ClientId> {
+        self.booking.as_ref().and_then(|b| b.counterparty())
     }
 
     fn deposit(
         &mut self,
-        client: &mut Client,
         amount: Amount,
         tx: TransactionId,
+        counterparty: Option<ClientId>,
     ) -> Result<(), ExecutionError> {
         // update balance
-        client.get_balance_mut().credit(amount)?;
+        self.balance.credit(amount)?;
 
         // add booking
-        client.add_or_update_booking(BookedDeposit::new(tx, amount));
+        self.booking = Some(
+            BookedTransaction::new(tx, self.asset, amount, Direction::Credit)
+                .with_counterparty(counterparty),
+        );
 
         Ok(())
     }
 
-    fn withdraw(&mut self, client: &mut Client, amount: Amount) -> Result<(), ExecutionError> {
+    fn withdraw(
+        &mut self,
+        amount: Amount,
+        tx: TransactionId,
+        counterparty: Option<ClientId>,
+    ) -> Result<(), ExecutionError> {
         // update balance
-        client.get_balance_mut().debit(amount)?;
+        self.balance.debit(amount)?;
+
+        // add booking
+        self.booking = Some(
+            BookedTransaction::new(tx, self.asset, amount, Direction::Debit)
+                .with_counterparty(counterparty),
+        );
+
+        Ok(())
+    }
+
+    /// applies a dispute, resolve or chargeback to a previously booked transaction
+    fn settle(&mut self, client: &Client, transaction: &Transaction) -> Result<(), ExecutionError> {
+        // try and get previously booked transaction
+        let mut booking = client.get_booking(&transaction.id)?.clone();
+        // a booking can only be referenced in the asset it has been booked in
+        if booking.asset() != transaction.asset {
+            return Err(InvalidBooking);
+        }
+        let amount = booking.amount();
+        let balance = &mut self.balance;
+
+        match (&transaction.transaction_type, booking.direction()) {
+            (TransactionType::Dispute, direction) => {
+                // check if disputable
+                booking.dispute()?;
+
+                match direction {
+                    // freeze the deposited amount
+                    Direction::Credit => balance.freeze(amount)?,
+                    // hold the withdrawn amount as it might be owed back to the client
+                    Direction::Debit => balance.hold(amount)?,
+                }
+            }
+            (TransactionType::Resolve, direction) => {
+                // check if resolvable
+                booking.resolve()?;
+
+                match direction {
+                    // unfreeze the deposited amount
+                    Direction::Credit => balance.unfreeze(amount)?,
+                    // the withdrawal stands, drop the held amount
+                    Direction::Debit => balance.release(amount)?,
+                }
+            }
+            (TransactionType::Chargeback, direction) => {
+                // check if chargeback is possible
+                booking.chargeback()?;
+
+                match direction {
+                    // remove the deposited amount
+                    Direction::Credit => balance.chargeback(amount)?,
+                    // re-credit the withdrawn amount to the client
+                    Direction::Debit => balance.unfreeze(amount)?,
+                }
+            }
+            _ => unreachable!("this path is only reachable through Dispute, Resolve or Chargeback"),
+        }
+
+        // update booking with cloned value
+        self.booking = Some(booking);
 
         Ok(())
     }
+
+    /// writes all staged changes to the client
+    fn commit(self, client: &mut Client) {
+        *client.get_balance_mut(self.asset) = self.balance;
+
+        if let Some(booking) = self.booking {
+            client.add_or_update_booking(booking);
+        }
+
+        if self.lock {
+            client.lock(LockReason::Chargeback);
+        }
+    }
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Transaction {
     id: TransactionId,
-    pub client: ClientId,
+    client: ClientId,
+    asset: Asset,
     transaction_type: TransactionType,
+    /// the time of the transaction given by the input, e.g. in seconds since the epoch
+    timestamp: Option<u64>,
 }
 
 impl Transaction {
+    /// creates a transaction in the default asset
     pub fn new(id: TransactionId, client: ClientId, transaction_type: TransactionType) -> Self {
         Self {
             client,
             transaction_type,
             id,
+            asset: Asset::default(),
+            timestamp: None,
         }
     }
+
+    /// books the transaction in the given asset instead
+    pub fn with_asset(mut self, asset: Asset) -> Self {
+        self.asset = asset;
+        self
+    }
+
+    /// sets the time of the transaction, which is only used by a [DisputeWindow::Age]
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn id(&self) -> TransactionId {
+        self.id
+    }
+
+    pub fn client(&self) -> ClientId {
+        self.client
+    }
+
+    pub fn asset(&self) -> Asset {
+        self.asset
+    }
+
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    pub fn transaction_type(&self) -> &TransactionType {
+        &self.transaction_type
+    }
+
+    /// deposits, withdrawals and transfers introduce a new, globally unique transaction id
+    pub fn is_new_transaction(&self) -> bool {
+        matches!(
+            self.transaction_type,
+            TransactionType::Deposit(_)
+                | TransactionType::Withdrawal(_)
+                | TransactionType::Transfer { .. }
+        )
+    }
+
+    /// administrative transactions change the account status instead of any balance
+    pub fn is_administrative(&self) -> bool {
+        matches!(
+            self.transaction_type,
+            TransactionType::Lock | TransactionType::Unlock | TransactionType::Close
+        )
+    }
 }
 
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum TransactionType {
     Deposit(Amount),
     Withdrawal(Amount),
+    /// moves funds from the transaction's client to another client
+    Transfer {
+        to: ClientId,
+        amount: Amount,
+    },
     Dispute,
     Resolve,
     Chargeback,
+    /// locks an active client manually
+    Lock,
+    /// unlocks a client regardless of why it has been locked
+    Unlock,
+    /// closes a client that does not hold any funds
+    Close,
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::asset::Asset;
     use crate::balance::Balance;
     use crate::client::ExecutionError::ClientLocked;
-    use crate::client::{Client, ClientId};
+    use crate::client::{Client, ClientId, LockReason};
     use crate::payment_engine::TransactionType::Deposit;
-    use crate::payment_engine::{ClientMap, PaymentEngine, Transaction};
+    use crate::payment_engine::{PaymentEngine, Transaction};
+    use crate::store::MemoryStore;
 
     #[test]
     fn cannot_operate_on_locked_account() {
@@ -159,22 +806,107 @@ mod tests {
 
         // create initial balance for the client
         let mut engine = engine_with_client(CLIENT, Balance::default());
-        engine.clients.get_mut(&CLIENT).unwrap().lock();
+        engine
+            .clients
+            .client_mut(CLIENT)
+            .unwrap()
+            .lock(LockReason::Manual);
 
         assert_eq!(
             engine
-                .execute(Transaction {
-                    id: 1,
-                    client: CLIENT,
-                    transaction_type: Deposit(100)
-                })
+                .execute(Transaction::new(1, CLIENT, Deposit(100)))
                 .unwrap_err(),
             ClientLocked
         );
     }
 
+    #[cfg(test)]
+    mod duplicate {
+        use crate::asset::Asset;
+        use crate::balance::ArithmeticError::Underflow;
+        use crate::client::ClientId;
+        use crate::client::ExecutionError::{Arithmetic, DuplicateTransaction};
+        use crate::payment_engine::TransactionType::{Deposit, Withdrawal};
+        use crate::payment_engine::{PaymentEngine, Transaction};
+
+        #[test]
+        fn replayed_deposit_is_rejected() {
+            const CLIENT: ClientId = 1;
+
+            let mut engine = PaymentEngine::default();
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(100)))
+                .is_ok());
+
+            assert_eq!(
+                engine.execute(Transaction::new(1, CLIENT, Deposit(100))),
+                Err(DuplicateTransaction)
+            );
+
+            let client = engine.client(CLIENT).unwrap().unwrap();
+            assert_eq!(client.balance(&Asset::default()).unwrap().available(), 100);
+        }
+
+        #[test]
+        fn withdrawal_cannot_reuse_deposit_id() {
+            const CLIENT: ClientId = 1;
+
+            let mut engine = PaymentEngine::default();
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(100)))
+                .is_ok());
+
+            assert_eq!(
+                engine.execute(Transaction::new(1, CLIENT, Withdrawal(50))),
+                Err(DuplicateTransaction)
+            );
+
+            let client = engine.client(CLIENT).unwrap().unwrap();
+            assert_eq!(client.balance(&Asset::default()).unwrap().available(), 100);
+        }
+
+        #[test]
+        fn replayed_withdrawal_is_rejected_across_clients() {
+            const CLIENT: ClientId = 1;
+            const OTHER_CLIENT: ClientId = 2;
+
+            let mut engine = PaymentEngine::default();
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(100)))
+                .is_ok());
+            assert!(engine
+                .execute(Transaction::new(2, CLIENT, Withdrawal(10)))
+                .is_ok());
+
+            assert_eq!(
+                engine.execute(Transaction::new(2, OTHER_CLIENT, Deposit(100))),
+                Err(DuplicateTransaction)
+            );
+            assert!(engine.client(OTHER_CLIENT).unwrap().is_none());
+        }
+
+        #[test]
+        fn failed_transaction_does_not_register_id() {
+            const CLIENT: ClientId = 1;
+
+            let mut engine = PaymentEngine::default();
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(100)))
+                .is_ok());
+            assert_eq!(
+                engine.execute(Transaction::new(2, CLIENT, Withdrawal(200))),
+                Err(Arithmetic(Underflow))
+            );
+
+            assert!(engine
+                .execute(Transaction::new(2, CLIENT, Withdrawal(50)))
+                .is_ok());
+        }
+    }
+
     #[cfg(test)]
     mod deposit {
+        use crate::asset::Asset;
         use crate::balance::ArithmeticError::Overflow;
         use crate::balance::{Amount, Balance};
         use crate::client::ClientId;
@@ -189,14 +921,10 @@ mod tests {
 
             let mut engine = PaymentEngine::default();
             assert!(engine
-                .execute(Transaction {
-                    transaction_type: Deposit(100),
-                    client: CLIENT,
-                    id: 1,
-                })
+                .execute(Transaction::new(1, CLIENT, Deposit(100)))
                 .is_ok());
 
-            assert!(engine.clients.contains_key(&CLIENT));
+            assert!(engine.client(CLIENT).unwrap().is_some());
         }
 
         #[test]
@@ -206,15 +934,14 @@ mod tests {
 
             let mut engine = PaymentEngine::default();
             assert!(engine
-                .execute(Transaction {
-                    transaction_type: Deposit(50),
-                    client: CLIENT,
-                    id: 1,
-                })
+                .execute(Transaction::new(1, CLIENT, Deposit(50)))
                 .is_ok());
 
-            let client = engine.clients.get(&CLIENT).unwrap();
-            assert_eq!(client.balance().available(), DEPOSIT);
+            let client = engine.client(CLIENT).unwrap().unwrap();
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().available(),
+                DEPOSIT
+            );
         }
 
         #[test]
@@ -226,21 +953,21 @@ mod tests {
 
             let mut engine = engine_with_client(CLIENT, init_balance);
             assert_eq!(
-                engine.execute(Transaction {
-                    transaction_type: Deposit(50),
-                    client: CLIENT,
-                    id: 1,
-                }),
+                engine.execute(Transaction::new(1, CLIENT, Deposit(50))),
                 Err(Arithmetic(Overflow))
             );
 
-            let client = engine.clients.get(&CLIENT).unwrap();
-            assert_eq!(client.balance().available(), Amount::MAX);
+            let client = engine.client(CLIENT).unwrap().unwrap();
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().available(),
+                Amount::MAX
+            );
         }
     }
 
     #[cfg(test)]
     mod withdrawal {
+        use crate::asset::Asset;
         use crate::balance::ArithmeticError::Underflow;
         use crate::balance::{Amount, Balance};
         use crate::client::ClientId;
@@ -255,11 +982,7 @@ mod tests {
 
             let mut engine = PaymentEngine::default();
             assert_eq!(
-                engine.execute(Transaction {
-                    transaction_type: Withdrawal(100),
-                    client: CLIENT,
-                    id: 1,
-                }),
+                engine.execute(Transaction::new(1, CLIENT, Withdrawal(100))),
                 Err(ClientDoesNotExist)
             );
         }
@@ -276,15 +999,14 @@ mod tests {
             let mut engine = engine_with_client(CLIENT, init_balance);
 
             assert!(engine
-                .execute(Transaction {
-                    id: 1,
-                    client: CLIENT,
-                    transaction_type: Withdrawal(WITHDRAW),
-                })
+                .execute(Transaction::new(1, CLIENT, Withdrawal(WITHDRAW)))
                 .is_ok());
 
-            let client = engine.clients.get(&CLIENT).unwrap();
-            assert_eq!(client.balance().available(), BALANCE - WITHDRAW);
+            let client = engine.client(CLIENT).unwrap().unwrap();
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().available(),
+                BALANCE - WITHDRAW
+            );
         }
 
         #[test]
@@ -299,24 +1021,24 @@ mod tests {
             let mut engine = engine_with_client(CLIENT, init_balance);
 
             assert_eq!(
-                engine.execute(Transaction {
-                    id: 1,
-                    client: CLIENT,
-                    transaction_type: Withdrawal(WITHDRAW),
-                }),
+                engine.execute(Transaction::new(1, CLIENT, Withdrawal(WITHDRAW))),
                 Err(Arithmetic(Underflow))
             );
 
-            let client = engine.clients.get(&CLIENT).unwrap();
-            assert_eq!(client.balance().available(), BALANCE);
+            let client = engine.client(CLIENT).unwrap().unwrap();
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().available(),
+                BALANCE
+            );
         }
     }
 
     #[cfg(test)]
     mod dispute {
+        use crate::asset::Asset;
         use crate::balance::{Amount, Balance};
         use crate::client::ExecutionError::{ClientDoesNotExist, InvalidState};
-        use crate::client::{BookedDeposit, ClientId, State, TransactionId};
+        use crate::client::{BookedTransaction, ClientId, Direction, State, TransactionId};
         use crate::payment_engine::tests::engine_with_client;
         use crate::payment_engine::TransactionType::{Deposit, Dispute};
         use crate::payment_engine::{PaymentEngine, Transaction};
@@ -327,11 +1049,7 @@ mod tests {
 
             let mut engine = PaymentEngine::default();
             assert_eq!(
-                engine.execute(Transaction {
-                    transaction_type: Dispute,
-                    client: CLIENT,
-                    id: 1,
-                }),
+                engine.execute(Transaction::new(1, CLIENT, Dispute)),
                 Err(ClientDoesNotExist)
             );
         }
@@ -344,26 +1062,18 @@ mod tests {
 
             let mut engine = PaymentEngine::default();
             assert!(engine
-                .execute(Transaction {
-                    transaction_type: Deposit(DEPOSIT),
-                    client: CLIENT,
-                    id: TRANSACTION,
-                })
+                .execute(Transaction::new(TRANSACTION, CLIENT, Deposit(DEPOSIT)))
                 .is_ok());
 
             assert!(engine
-                .execute(Transaction {
-                    id: TRANSACTION,
-                    client: CLIENT,
-                    transaction_type: Dispute,
-                })
+                .execute(Transaction::new(TRANSACTION, CLIENT, Dispute))
                 .is_ok());
 
-            let client = engine.clients.get_mut(&CLIENT).unwrap();
-            assert_eq!(client.balance().available(), 0);
-            assert_eq!(client.balance().frozen(), DEPOSIT);
+            let client = engine.clients.client_mut(CLIENT).unwrap();
+            assert_eq!(client.balance(&Asset::default()).unwrap().available(), 0);
+            assert_eq!(client.balance(&Asset::default()).unwrap().frozen(), DEPOSIT);
 
-            let booking = client.get_booking_mut(&TRANSACTION).unwrap();
+            let booking = client.get_booking(&TRANSACTION).unwrap();
             assert_eq!(*booking.state(), State::Disputed);
         }
 
@@ -379,40 +1089,41 @@ mod tests {
             let mut engine = engine_with_client(CLIENT, init_balance);
 
             // create a booking that is in state `Resolved`
-            let mut booking = BookedDeposit::new(TRANSACTION, DEPOSIT);
+            let mut booking =
+                BookedTransaction::new(TRANSACTION, Asset::default(), DEPOSIT, Direction::Credit);
             assert!(booking.dispute().is_ok());
             assert!(booking.resolve().is_ok());
             engine
                 .clients
-                .get_mut(&CLIENT)
+                .client_mut(CLIENT)
                 .unwrap()
                 .add_or_update_booking(booking);
 
             assert_eq!(
                 engine
-                    .execute(Transaction {
-                        id: TRANSACTION,
-                        client: CLIENT,
-                        transaction_type: Dispute,
-                    })
+                    .execute(Transaction::new(TRANSACTION, CLIENT, Dispute))
                     .unwrap_err(),
                 InvalidState
             );
 
-            let client = engine.clients.get_mut(&CLIENT).unwrap();
-            assert_eq!(client.balance().available(), DEPOSIT);
-            assert_eq!(client.balance().frozen(), 0);
+            let client = engine.clients.client_mut(CLIENT).unwrap();
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().available(),
+                DEPOSIT
+            );
+            assert_eq!(client.balance(&Asset::default()).unwrap().frozen(), 0);
 
-            let booking = client.get_booking_mut(&TRANSACTION).unwrap();
+            let booking = client.get_booking(&TRANSACTION).unwrap();
             assert_eq!(*booking.state(), State::Resolved);
         }
     }
 
     #[cfg(test)]
     mod resolve {
+        use crate::asset::Asset;
         use crate::balance::{Amount, Balance};
         use crate::client::ExecutionError::{ClientDoesNotExist, InvalidState};
-        use crate::client::{BookedDeposit, ClientId, State, TransactionId};
+        use crate::client::{BookedTransaction, ClientId, Direction, State, TransactionId};
         use crate::payment_engine::tests::engine_with_client;
         use crate::payment_engine::TransactionType::Resolve;
         use crate::payment_engine::{PaymentEngine, Transaction};
@@ -423,11 +1134,7 @@ mod tests {
 
             let mut engine = PaymentEngine::default();
             assert_eq!(
-                engine.execute(Transaction {
-                    transaction_type: Resolve,
-                    client: CLIENT,
-                    id: 1,
-                }),
+                engine.execute(Transaction::new(1, CLIENT, Resolve)),
                 Err(ClientDoesNotExist)
             );
         }
@@ -445,27 +1152,27 @@ mod tests {
             let mut engine = engine_with_client(CLIENT, init_balance);
 
             // create a booking that is in state `Disputed`
-            let mut booking = BookedDeposit::new(TRANSACTION, DEPOSIT);
+            let mut booking =
+                BookedTransaction::new(TRANSACTION, Asset::default(), DEPOSIT, Direction::Credit);
             assert!(booking.dispute().is_ok());
             engine
                 .clients
-                .get_mut(&CLIENT)
+                .client_mut(CLIENT)
                 .unwrap()
                 .add_or_update_booking(booking);
 
             assert!(engine
-                .execute(Transaction {
-                    id: TRANSACTION,
-                    client: CLIENT,
-                    transaction_type: Resolve,
-                })
+                .execute(Transaction::new(TRANSACTION, CLIENT, Resolve))
                 .is_ok());
 
-            let client = engine.clients.get_mut(&CLIENT).unwrap();
-            assert_eq!(client.balance().available(), DEPOSIT);
-            assert_eq!(client.balance().frozen(), 0);
+            let client = engine.clients.client_mut(CLIENT).unwrap();
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().available(),
+                DEPOSIT
+            );
+            assert_eq!(client.balance(&Asset::default()).unwrap().frozen(), 0);
 
-            let booking = client.get_booking_mut(&TRANSACTION).unwrap();
+            let booking = client.get_booking(&TRANSACTION).unwrap();
             assert_eq!(*booking.state(), State::Resolved);
         }
 
@@ -481,38 +1188,39 @@ mod tests {
             let mut engine = engine_with_client(CLIENT, init_balance);
 
             // create a booking that is in state `Booked`
-            let booking = BookedDeposit::new(TRANSACTION, DEPOSIT);
+            let booking =
+                BookedTransaction::new(TRANSACTION, Asset::default(), DEPOSIT, Direction::Credit);
             engine
                 .clients
-                .get_mut(&CLIENT)
+                .client_mut(CLIENT)
                 .unwrap()
                 .add_or_update_booking(booking);
 
             assert_eq!(
                 engine
-                    .execute(Transaction {
-                        id: TRANSACTION,
-                        client: CLIENT,
-                        transaction_type: Resolve,
-                    })
+                    .execute(Transaction::new(TRANSACTION, CLIENT, Resolve))
                     .unwrap_err(),
                 InvalidState
             );
 
-            let client = engine.clients.get_mut(&CLIENT).unwrap();
-            assert_eq!(client.balance().available(), DEPOSIT);
-            assert_eq!(client.balance().frozen(), 0);
+            let client = engine.clients.client_mut(CLIENT).unwrap();
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().available(),
+                DEPOSIT
+            );
+            assert_eq!(client.balance(&Asset::default()).unwrap().frozen(), 0);
 
-            let booking = client.get_booking_mut(&TRANSACTION).unwrap();
+            let booking = client.get_booking(&TRANSACTION).unwrap();
             assert_eq!(*booking.state(), State::Booked);
         }
     }
 
     #[cfg(test)]
     mod chargeback {
+        use crate::asset::Asset;
         use crate::balance::{Amount, Balance};
         use crate::client::ExecutionError::{ClientDoesNotExist, InvalidState};
-        use crate::client::{BookedDeposit, ClientId, State, TransactionId};
+        use crate::client::{BookedTransaction, ClientId, Direction, State, TransactionId};
         use crate::payment_engine::tests::engine_with_client;
         use crate::payment_engine::TransactionType::Chargeback;
         use crate::payment_engine::{PaymentEngine, Transaction};
@@ -523,11 +1231,7 @@ mod tests {
 
             let mut engine = PaymentEngine::default();
             assert_eq!(
-                engine.execute(Transaction {
-                    transaction_type: Chargeback,
-                    client: CLIENT,
-                    id: 1,
-                }),
+                engine.execute(Transaction::new(1, CLIENT, Chargeback)),
                 Err(ClientDoesNotExist)
             );
         }
@@ -545,27 +1249,24 @@ mod tests {
             let mut engine = engine_with_client(CLIENT, init_balance);
 
             // create a booking that is in state `Disputed`
-            let mut booking = BookedDeposit::new(TRANSACTION, DEPOSIT);
+            let mut booking =
+                BookedTransaction::new(TRANSACTION, Asset::default(), DEPOSIT, Direction::Credit);
             assert!(booking.dispute().is_ok());
             engine
                 .clients
-                .get_mut(&CLIENT)
+                .client_mut(CLIENT)
                 .unwrap()
                 .add_or_update_booking(booking);
 
             assert!(engine
-                .execute(Transaction {
-                    id: TRANSACTION,
-                    client: CLIENT,
-                    transaction_type: Chargeback,
-                })
+                .execute(Transaction::new(TRANSACTION, CLIENT, Chargeback))
                 .is_ok());
 
-            let client = engine.clients.get_mut(&CLIENT).unwrap();
-            assert_eq!(client.balance().available(), 0);
-            assert_eq!(client.balance().frozen(), 0);
+            let client = engine.clients.client_mut(CLIENT).unwrap();
+            assert_eq!(client.balance(&Asset::default()).unwrap().available(), 0);
+            assert_eq!(client.balance(&Asset::default()).unwrap().frozen(), 0);
 
-            let booking = client.get_booking_mut(&TRANSACTION).unwrap();
+            let booking = client.get_booking(&TRANSACTION).unwrap();
             assert_eq!(*booking.state(), State::Chargeback);
 
             assert!(client.is_locked());
@@ -583,42 +1284,1163 @@ mod tests {
             let mut engine = engine_with_client(CLIENT, init_balance);
 
             // create a booking that is in state `Booked`
-            let booking = BookedDeposit::new(TRANSACTION, DEPOSIT);
+            let booking =
+                BookedTransaction::new(TRANSACTION, Asset::default(), DEPOSIT, Direction::Credit);
             engine
                 .clients
-                .get_mut(&CLIENT)
+                .client_mut(CLIENT)
                 .unwrap()
                 .add_or_update_booking(booking);
 
             assert_eq!(
                 engine
-                    .execute(Transaction {
-                        id: TRANSACTION,
-                        client: CLIENT,
-                        transaction_type: Chargeback,
-                    })
+                    .execute(Transaction::new(TRANSACTION, CLIENT, Chargeback))
                     .unwrap_err(),
                 InvalidState
             );
 
-            let client = engine.clients.get_mut(&CLIENT).unwrap();
-            assert_eq!(client.balance().available(), DEPOSIT);
-            assert_eq!(client.balance().frozen(), 0);
+            let client = engine.clients.client_mut(CLIENT).unwrap();
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().available(),
+                DEPOSIT
+            );
+            assert_eq!(client.balance(&Asset::default()).unwrap().frozen(), 0);
 
-            let booking = client.get_booking_mut(&TRANSACTION).unwrap();
+            let booking = client.get_booking(&TRANSACTION).unwrap();
             assert_eq!(*booking.state(), State::Booked);
 
             assert!(!client.is_locked());
         }
     }
 
+    #[cfg(test)]
+    mod withdrawal_dispute {
+        use crate::asset::Asset;
+        use crate::balance::Amount;
+        use crate::client::{ClientId, State, TransactionId};
+        use crate::payment_engine::TransactionType::{
+            Chargeback, Deposit, Dispute, Resolve, Withdrawal,
+        };
+        use crate::payment_engine::{PaymentEngine, Transaction};
+
+        const CLIENT: ClientId = 1;
+        const DEPOSIT: Amount = 100;
+        const WITHDRAW: Amount = 40;
+        const TRANSACTION: TransactionId = 2;
+
+        fn engine_with_disputed_withdrawal() -> PaymentEngine {
+            let mut engine = PaymentEngine::default();
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(DEPOSIT)))
+                .is_ok());
+            assert!(engine
+                .execute(Transaction::new(TRANSACTION, CLIENT, Withdrawal(WITHDRAW)))
+                .is_ok());
+            assert!(engine
+                .execute(Transaction::new(TRANSACTION, CLIENT, Dispute))
+                .is_ok());
+
+            engine
+        }
+
+        #[test]
+        fn dispute_holds_withdrawn_amount() {
+            let mut engine = engine_with_disputed_withdrawal();
+
+            let client = engine.clients.client_mut(CLIENT).unwrap();
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().available(),
+                DEPOSIT - WITHDRAW
+            );
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().frozen(),
+                WITHDRAW
+            );
+
+            let booking = client.get_booking(&TRANSACTION).unwrap();
+            assert_eq!(*booking.state(), State::Disputed);
+        }
+
+        #[test]
+        fn resolve_releases_held_amount() {
+            let mut engine = engine_with_disputed_withdrawal();
+            assert!(engine
+                .execute(Transaction::new(TRANSACTION, CLIENT, Resolve))
+                .is_ok());
+
+            let client = engine.clients.client_mut(CLIENT).unwrap();
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().available(),
+                DEPOSIT - WITHDRAW
+            );
+            assert_eq!(client.balance(&Asset::default()).unwrap().frozen(), 0);
+
+            let booking = client.get_booking(&TRANSACTION).unwrap();
+            assert_eq!(*booking.state(), State::Resolved);
+        }
+
+        #[test]
+        fn chargeback_re_credits_withdrawn_amount() {
+            let mut engine = engine_with_disputed_withdrawal();
+            assert!(engine
+                .execute(Transaction::new(TRANSACTION, CLIENT, Chargeback))
+                .is_ok());
+
+            let client = engine.clients.client_mut(CLIENT).unwrap();
+            assert_eq!(
+                client.balance(&Asset::default()).unwrap().available(),
+                DEPOSIT
+            );
+            assert_eq!(client.balance(&Asset::default()).unwrap().frozen(), 0);
+            assert!(client.is_locked());
+
+            let booking = client.get_booking(&TRANSACTION).unwrap();
+            assert_eq!(*booking.state(), State::Chargeback);
+        }
+    }
+
+    #[cfg(test)]
+    mod query {
+        use crate::asset::Asset;
+        use crate::client::{ClientId, State};
+        use crate::payment_engine::TransactionType::{Chargeback, Deposit, Dispute, Withdrawal};
+        use crate::payment_engine::{AssetTotals, PaymentEngine, Totals, Transaction};
+        use std::collections::BTreeMap;
+
+        const CLIENT: ClientId = 1;
+        const OTHER_CLIENT: ClientId = 2;
+
+        fn engine() -> PaymentEngine {
+            let mut engine = PaymentEngine::default();
+            for transaction in [
+                Transaction::new(1, CLIENT, Deposit(100)),
+                Transaction::new(2, CLIENT, Withdrawal(30)),
+                Transaction::new(3, OTHER_CLIENT, Deposit(50)),
+                Transaction::new(4, OTHER_CLIENT, Deposit(20)),
+                Transaction::new(3, OTHER_CLIENT, Dispute),
+                Transaction::new(3, OTHER_CLIENT, Chargeback),
+                Transaction::new(2, CLIENT, Dispute),
+            ] {
+                assert!(engine.execute(transaction).is_ok());
+            }
+
+            engine
+        }
+
+        #[test]
+        fn client_lookup() {
+            let engine = engine();
+
+            assert_eq!(
+                engine
+                    .client(CLIENT)
+                    .unwrap()
+                    .unwrap()
+                    .balance(&Asset::default())
+                    .unwrap()
+                    .available(),
+                70
+            );
+            assert!(engine.client(OTHER_CLIENT).unwrap().unwrap().is_locked());
+            assert!(engine.client(3).unwrap().is_none());
+
+            let mut ids: Vec<ClientId> = engine.clients().map(|c| c.unwrap().id()).collect();
+            ids.sort();
+            assert_eq!(ids, vec![CLIENT, OTHER_CLIENT]);
+        }
+
+        #[test]
+        fn booking_lookup() {
+            let engine = engine();
+
+            let (client, booking) = engine.booking(2).unwrap().unwrap();
+            assert_eq!(client, CLIENT);
+            assert_eq!(*booking.state(), State::Disputed);
+
+            let (client, booking) = engine.booking(3).unwrap().unwrap();
+            assert_eq!(client, OTHER_CLIENT);
+            assert_eq!(*booking.state(), State::Chargeback);
+
+            assert_eq!(
+                *engine.booking(1).unwrap().unwrap().1.state(),
+                State::Booked
+            );
+            assert!(engine.booking(5).unwrap().is_none());
+        }
+
+        #[test]
+        fn totals() {
+            assert_eq!(
+                engine().totals().unwrap(),
+                Totals {
+                    assets: BTreeMap::from([(
+                        Asset::default(),
+                        AssetTotals {
+                            available: 90,
+                            held: 30,
+                            total: 120,
+                        }
+                    )]),
+                    locked: 1,
+                    closed: 0,
+                }
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod multi_asset {
+        use crate::asset::Asset;
+        use crate::balance::ArithmeticError::Underflow;
+        use crate::client::ClientId;
+        use crate::client::ExecutionError::{Arithmetic, ClientLocked, InvalidBooking};
+        use crate::payment_engine::TransactionType::{Chargeback, Deposit, Dispute, Withdrawal};
+        use crate::payment_engine::{PaymentEngine, Transaction};
+
+        const CLIENT: ClientId = 1;
+
+        fn usd() -> Asset {
+            "USD".parse().unwrap()
+        }
+
+        fn btc() -> Asset {
+            "BTC".parse().unwrap()
+        }
+
+        fn engine() -> PaymentEngine {
+            let mut engine = PaymentEngine::default();
+            for transaction in [
+                Transaction::new(1, CLIENT, Deposit(100)).with_asset(usd()),
+                Transaction::new(2, CLIENT, Deposit(5)).with_asset(btc()),
+            ] {
+                assert!(engine.execute(transaction).is_ok());
+            }
+
+            engine
+        }
+
+        #[test]
+        fn keeps_one_balance_per_asset() {
+            let engine = engine();
+
+            let client = engine.client(CLIENT).unwrap().unwrap();
+            assert_eq!(client.balance(&usd()).unwrap().available(), 100);
+            assert_eq!(client.balance(&btc()).unwrap().available(), 5);
+            assert!(client.balance(&Asset::default()).is_none());
+
+            let assets: Vec<Asset> = client.balances().map(|(asset, _)| *asset).collect();
+            assert_eq!(assets, vec![btc(), usd()]);
+        }
+
+        #[test]
+        fn cannot_withdraw_from_another_asset() {
+            let mut engine = engine();
+
+            assert_eq!(
+                engine.execute(Transaction::new(3, CLIENT, Withdrawal(50)).with_asset(btc())),
+                Err(Arithmetic(Underflow))
+            );
+            assert!(engine
+                .execute(Transaction::new(3, CLIENT, Withdrawal(50)).with_asset(usd()))
+                .is_ok());
+
+            let client = engine.client(CLIENT).unwrap().unwrap();
+            assert_eq!(client.balance(&usd()).unwrap().available(), 50);
+            assert_eq!(client.balance(&btc()).unwrap().available(), 5);
+        }
+
+        #[test]
+        fn dispute_must_reference_the_booked_asset() {
+            let mut engine = engine();
+
+            assert_eq!(
+                engine.execute(Transaction::new(1, CLIENT, Dispute).with_asset(btc())),
+                Err(InvalidBooking)
+            );
+            assert_eq!(
+                engine.execute(Transaction::new(1, CLIENT, Dispute)),
+                Err(InvalidBooking)
+            );
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Dispute).with_asset(usd()))
+                .is_ok());
+
+            let client = engine.client(CLIENT).unwrap().unwrap();
+            assert_eq!(client.balance(&usd()).unwrap().frozen(), 100);
+            assert_eq!(client.balance(&btc()).unwrap().frozen(), 0);
+            assert!(client.balance(&Asset::default()).is_none());
+        }
+
+        #[test]
+        fn chargeback_locks_all_assets() {
+            let mut engine = engine();
+            for transaction in [
+                Transaction::new(2, CLIENT, Dispute).with_asset(btc()),
+                Transaction::new(2, CLIENT, Chargeback).with_asset(btc()),
+            ] {
+                assert!(engine.execute(transaction).is_ok());
+            }
+
+            assert_eq!(
+                engine.execute(Transaction::new(3, CLIENT, Deposit(10)).with_asset(usd())),
+                Err(ClientLocked)
+            );
+
+            let client = engine.client(CLIENT).unwrap().unwrap();
+            assert!(client.is_locked());
+            assert_eq!(client.balance(&usd()).unwrap().available(), 100);
+            assert_eq!(client.balance(&btc()).unwrap().available(), 0);
+        }
+
+        #[test]
+        fn totals_per_asset() {
+            let mut engine = engine();
+            assert!(engine
+                .execute(Transaction::new(3, 2, Deposit(20)).with_asset(usd()))
+                .is_ok());
+
+            let totals = engine.totals().unwrap();
+            assert_eq!(totals.assets[&usd()].total, 120);
+            assert_eq!(totals.assets[&btc()].total, 5);
+            assert_eq!(totals.assets.len(), 2);
+        }
+    }
+
+    #[cfg(test)]
+    mod transfer {
+        use crate::asset::Asset;
+        use crate::balance::ArithmeticError::Underflow;
+        use crate::balance::{Amount, Balance};
+        use crate::client::ExecutionError::{
+            Arithmetic, ClientDoesNotExist, ClientLocked, SelfTransfer,
+        };
+        use crate::client::{ClientId, Direction, LockReason, State, TransactionId};
+        use crate::payment_engine::TransactionType::{
+            Chargeback, Deposit, Dispute, Resolve, Transfer,
+        };
+        use crate::payment_engine::{PaymentEngine, Transaction, TransactionType};
+
+        const SENDER: ClientId = 1;
+        const RECEIVER: ClientId = 2;
+        const DEPOSIT: Amount = 100;
+        const TRANSFER: Amount = 40;
+        const TRANSACTION: TransactionId = 3;
+
+        fn engine() -> PaymentEngine {
+            let mut engine = PaymentEngine::default();
+            for transaction in [
+                Transaction::new(1, SENDER, Deposit(DEPOSIT)),
+                Transaction::new(2, RECEIVER, Deposit(DEPOSIT)),
+            ] {
+                assert!(engine.execute(transaction).is_ok());
+            }
+
+            engine
+        }
+
+        fn engine_with_transfer() -> PaymentEngine {
+            let mut engine = engine();
+            assert!(engine
+                .execute(Transaction::new(TRANSACTION, SENDER, transfer(RECEIVER)))
+                .is_ok());
+
+            engine
+        }
+
+        fn transfer(to: ClientId) -> TransactionType {
+            Transfer {
+                to,
+                amount: TRANSFER,
+            }
+        }
+
+        fn balance(engine: &PaymentEngine, client: ClientId) -> Balance {
+            engine
+                .client(client)
+                .unwrap()
+                .unwrap()
+                .balance(&Asset::default())
+                .cloned()
+                .unwrap()
+        }
+
+        #[test]
+        fn moves_funds_between_clients() {
+            let engine = engine_with_transfer();
+
+            assert_eq!(balance(&engine, SENDER).available(), DEPOSIT - TRANSFER);
+            assert_eq!(balance(&engine, RECEIVER).available(), DEPOSIT + TRANSFER);
+
+            let sender = engine.client(SENDER).unwrap().unwrap();
+            let sent = sender.get_booking(&TRANSACTION).unwrap();
+            assert_eq!(sent.direction(), Direction::Debit);
+            assert_eq!(sent.counterparty(), Some(RECEIVER));
+            let receiver = engine.client(RECEIVER).unwrap().unwrap();
+            let received = receiver.get_booking(&TRANSACTION).unwrap();
+            assert_eq!(received.direction(), Direction::Credit);
+            assert_eq!(received.counterparty(), Some(SENDER));
+        }
+
+        #[test]
+        fn creates_receiver() {
+            let mut engine = engine();
+            assert!(engine
+                .execute(Transaction::new(TRANSACTION, SENDER, transfer(7)))
+                .is_ok());
+
+            assert_eq!(balance(&engine, 7).available(), TRANSFER);
+        }
+
+        #[test]
+        fn failed_transfer_updates_neither_client() {
+            let mut engine = engine();
+
+            let overdrawn = Transfer {
+                to: RECEIVER,
+                amount: DEPOSIT + 1,
+            };
+            assert_eq!(
+                engine.execute(Transaction::new(TRANSACTION, SENDER, overdrawn)),
+                Err(Arithmetic(Underflow))
+            );
+            assert_eq!(
+                engine.execute(Transaction::new(TRANSACTION, SENDER, transfer(SENDER))),
+                Err(SelfTransfer)
+            );
+            assert_eq!(
+                engine.execute(Transaction::new(TRANSACTION, 7, transfer(SENDER))),
+                Err(ClientDoesNotExist)
+            );
+
+            engine
+                .clients
+                .client_mut(RECEIVER)
+                .unwrap()
+                .lock(LockReason::Manual);
+            assert_eq!(
+                engine.execute(Transaction::new(TRANSACTION, SENDER, transfer(RECEIVER))),
+                Err(ClientLocked)
+            );
+
+            assert_eq!(balance(&engine, SENDER).available(), DEPOSIT);
+            assert_eq!(balance(&engine, RECEIVER).available(), DEPOSIT);
+            assert!(!engine.has_transaction(&TRANSACTION));
+        }
+
+        #[test]
+        fn dispute_and_resolve_update_both_sides() {
+            let mut engine = engine_with_transfer();
+            assert!(engine
+                .execute(Transaction::new(TRANSACTION, RECEIVER, Dispute))
+                .is_ok());
+
+            assert_eq!(balance(&engine, SENDER).frozen(), TRANSFER);
+            assert_eq!(balance(&engine, RECEIVER).available(), DEPOSIT);
+            assert_eq!(balance(&engine, RECEIVER).frozen(), TRANSFER);
+
+            assert!(engine
+                .execute(Transaction::new(TRANSACTION, SENDER, Resolve))
+                .is_ok());
+
+            assert_eq!(balance(&engine, SENDER).available(), DEPOSIT - TRANSFER);
+            assert_eq!(balance(&engine, SENDER).frozen(), 0);
+            assert_eq!(balance(&engine, RECEIVER).available(), DEPOSIT + TRANSFER);
+            assert_eq!(balance(&engine, RECEIVER).frozen(), 0);
+        }
+
+        #[test]
+        fn chargeback_restores_both_sides() {
+            let mut engine = engine_with_transfer();
+            for transaction in [
+                Transaction::new(TRANSACTION, SENDER, Dispute),
+                Transaction::new(TRANSACTION, SENDER, Chargeback),
+            ] {
+                assert!(engine.execute(transaction).is_ok());
+            }
+
+            assert_eq!(balance(&engine, SENDER).available(), DEPOSIT);
+            assert_eq!(balance(&engine, SENDER).frozen(), 0);
+            assert_eq!(balance(&engine, RECEIVER).available(), DEPOSIT);
+            assert_eq!(balance(&engine, RECEIVER).frozen(), 0);
+
+            // only the client initiating the chargeback is locked
+            assert!(engine.client(SENDER).unwrap().unwrap().is_locked());
+            assert!(!engine.client(RECEIVER).unwrap().unwrap().is_locked());
+
+            let receiver = engine.client(RECEIVER).unwrap().unwrap();
+            let booking = receiver.get_booking(&TRANSACTION);
+            assert_eq!(*booking.unwrap().state(), State::Chargeback);
+        }
+
+        #[test]
+        fn cannot_dispute_with_locked_counterparty() {
+            let mut engine = engine_with_transfer();
+            engine
+                .clients
+                .client_mut(RECEIVER)
+                .unwrap()
+                .lock(LockReason::Manual);
+
+            assert_eq!(
+                engine.execute(Transaction::new(TRANSACTION, SENDER, Dispute)),
+                Err(ClientLocked)
+            );
+            assert_eq!(balance(&engine, SENDER).frozen(), 0);
+        }
+    }
+
+    #[cfg(test)]
+    mod administration {
+        use crate::client::ExecutionError::{
+            ClientClosed, ClientDoesNotExist, ClientLocked, InvalidStatus, NonZeroBalance,
+        };
+        use crate::client::{AccountStatus, ClientId, LockReason};
+        use crate::payment_engine::TransactionType::{
+            Chargeback, Close, Deposit, Dispute, Lock, Transfer, Unlock, Withdrawal,
+        };
+        use crate::payment_engine::{PaymentEngine, Transaction, TransactionType};
+
+        const CLIENT: ClientId = 1;
+
+        fn engine() -> PaymentEngine {
+            let mut engine = PaymentEngine::default();
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(100)))
+                .is_ok());
+
+            engine
+        }
+
+        fn admin(
+            engine: &mut PaymentEngine,
+            transaction_type: TransactionType,
+        ) -> Result<(), crate::client::ExecutionError> {
+            engine.execute(Transaction::new(0, CLIENT, transaction_type))
+        }
+
+        fn status(engine: &PaymentEngine) -> AccountStatus {
+            engine.client(CLIENT).unwrap().unwrap().status()
+        }
+
+        #[test]
+        fn manual_lock_rejects_transactions_until_unlocked() {
+            let mut engine = engine();
+
+            assert!(admin(&mut engine, Lock).is_ok());
+            assert_eq!(status(&engine), AccountStatus::Locked(LockReason::Manual));
+            assert_eq!(
+                engine.execute(Transaction::new(2, CLIENT, Deposit(10))),
+                Err(ClientLocked)
+            );
+
+            assert!(admin(&mut engine, Unlock).is_ok());
+            assert_eq!(status(&engine), AccountStatus::Active);
+            assert!(engine
+                .execute(Transaction::new(2, CLIENT, Deposit(10)))
+                .is_ok());
+        }
+
+        #[test]
+        fn unlocks_after_chargeback() {
+            let mut engine = engine();
+            for transaction in [
+                Transaction::new(1, CLIENT, Dispute),
+                Transaction::new(1, CLIENT, Chargeback),
+            ] {
+                assert!(engine.execute(transaction).is_ok());
+            }
+            assert_eq!(
+                status(&engine),
+                AccountStatus::Locked(LockReason::Chargeback)
+            );
+
+            assert!(admin(&mut engine, Unlock).is_ok());
+            assert_eq!(status(&engine), AccountStatus::Active);
+        }
+
+        #[test]
+        fn close_requires_zero_balance() {
+            let mut engine = engine();
+
+            assert_eq!(admin(&mut engine, Close), Err(NonZeroBalance));
+            assert!(engine
+                .execute(Transaction::new(2, CLIENT, Withdrawal(100)))
+                .is_ok());
+            assert!(admin(&mut engine, Close).is_ok());
+            assert_eq!(status(&engine), AccountStatus::Closed);
+
+            assert_eq!(
+                engine.execute(Transaction::new(3, CLIENT, Deposit(10))),
+                Err(ClientClosed)
+            );
+            assert_eq!(engine.execute(Transaction::new(3, 2, Deposit(10))), Ok(()));
+            assert_eq!(
+                engine.execute(Transaction::new(
+                    4,
+                    2,
+                    Transfer {
+                        to: CLIENT,
+                        amount: 1
+                    }
+                )),
+                Err(ClientClosed)
+            );
+            assert_eq!(admin(&mut engine, Unlock), Err(ClientClosed));
+        }
+
+        #[test]
+        fn rejects_invalid_status_changes() {
+            let mut engine = engine();
+
+            assert_eq!(admin(&mut engine, Unlock), Err(InvalidStatus));
+            assert!(admin(&mut engine, Lock).is_ok());
+            assert_eq!(admin(&mut engine, Lock), Err(InvalidStatus));
+            assert_eq!(
+                engine.execute(Transaction::new(0, 2, Lock)),
+                Err(ClientDoesNotExist)
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod dispute_window {
+        use crate::asset::Asset;
+        use crate::client::ExecutionError::{DisputeWindowExpired, InvalidBooking};
+        use crate::client::{ClientId, State};
+        use crate::dispute_window::DisputeWindow;
+        use crate::payment_engine::TransactionType::{
+            Deposit, Dispute, Resolve, Transfer, Withdrawal,
+        };
+        use crate::payment_engine::{PaymentEngine, Transaction};
+
+        const CLIENT: ClientId = 1;
+        const OTHER_CLIENT: ClientId = 2;
+
+        fn engine(window: DisputeWindow) -> PaymentEngine {
+            PaymentEngine::default().with_dispute_window(window)
+        }
+
+        #[test]
+        fn evicts_bookings_after_subsequent_transactions() {
+            let mut engine = engine(DisputeWindow::Transactions(2));
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(100)))
+                .is_ok());
+            assert!(engine
+                .execute(Transaction::new(2, CLIENT, Deposit(100)))
+                .is_ok());
+            // rejected transactions do not count
+            assert!(engine
+                .execute(Transaction::new(3, CLIENT, Withdrawal(1000)))
+                .is_err());
+            assert!(engine
+                .execute(Transaction::new(4, OTHER_CLIENT, Deposit(100)))
+                .is_ok());
+
+            assert_eq!(
+                engine.execute(Transaction::new(1, CLIENT, Dispute)),
+                Err(DisputeWindowExpired)
+            );
+            assert!(engine.execute(Transaction::new(2, CLIENT, Dispute)).is_ok());
+            // unknown bookings are still reported as such
+            assert_eq!(
+                engine.execute(Transaction::new(3, CLIENT, Dispute)),
+                Err(InvalidBooking)
+            );
+
+            let client = engine.client(CLIENT).unwrap().unwrap();
+            assert_eq!(client.bookings().count(), 1);
+            let balance = client.balance(&Asset::default()).unwrap();
+            assert_eq!(balance.available(), 100);
+            assert_eq!(balance.frozen(), 100);
+        }
+
+        #[test]
+        fn keeps_disputed_bookings() {
+            let mut engine = engine(DisputeWindow::Transactions(1));
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(100)))
+                .is_ok());
+            assert!(engine.execute(Transaction::new(1, CLIENT, Dispute)).is_ok());
+            for tx in 2..5 {
+                assert!(engine
+                    .execute(Transaction::new(tx, OTHER_CLIENT, Deposit(100)))
+                    .is_ok());
+            }
+
+            assert!(engine.execute(Transaction::new(1, CLIENT, Resolve)).is_ok());
+            let (_, booking) = engine.booking(1).unwrap().unwrap();
+            assert_eq!(booking.state(), &State::Resolved);
+        }
+
+        #[test]
+        fn evicts_both_sides_of_a_transfer() {
+            let mut engine = engine(DisputeWindow::Transactions(1));
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(100)))
+                .is_ok());
+            assert!(engine
+                .execute(Transaction::new(
+                    2,
+                    CLIENT,
+                    Transfer {
+                        to: OTHER_CLIENT,
+                        amount: 50
+                    }
+                ))
+                .is_ok());
+            assert!(engine
+                .execute(Transaction::new(3, CLIENT, Deposit(100)))
+                .is_ok());
+
+            for client in [CLIENT, OTHER_CLIENT] {
+                assert_eq!(
+                    engine.execute(Transaction::new(2, client, Dispute)),
+                    Err(DisputeWindowExpired)
+                );
+            }
+            assert_eq!(
+                engine
+                    .client(OTHER_CLIENT)
+                    .unwrap()
+                    .unwrap()
+                    .bookings()
+                    .count(),
+                0
+            );
+        }
+
+        #[test]
+        fn evicts_bookings_by_timestamp() {
+            let mut engine = engine(DisputeWindow::Age(60));
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(100)).with_timestamp(1_000))
+                .is_ok());
+            assert!(engine
+                .execute(Transaction::new(2, CLIENT, Deposit(100)).with_timestamp(1_030))
+                .is_ok());
+
+            // a dispute at the end of the window is accepted
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Dispute).with_timestamp(1_060))
+                .is_ok());
+            assert_eq!(
+                engine.execute(Transaction::new(2, CLIENT, Dispute).with_timestamp(1_091)),
+                Err(DisputeWindowExpired)
+            );
+        }
+
+        #[test]
+        fn rejected_transactions_do_not_move_the_clock() {
+            let mut engine = engine(DisputeWindow::Age(60));
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(100)).with_timestamp(1_000))
+                .is_ok());
+            assert!(engine
+                .execute(Transaction::new(2, CLIENT, Withdrawal(1_000)).with_timestamp(9_999))
+                .is_err());
+
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Dispute).with_timestamp(1_010))
+                .is_ok());
+        }
+
+        #[test]
+        fn dispute_expires_at_its_own_time() {
+            let mut engine = engine(DisputeWindow::Age(60));
+            assert!(engine
+                .execute(Transaction::new(1, CLIENT, Deposit(100)).with_timestamp(1_000))
+                .is_ok());
+
+            assert_eq!(
+                engine.execute(Transaction::new(1, CLIENT, Dispute).with_timestamp(1_061)),
+                Err(DisputeWindowExpired)
+            );
+            // the rejected dispute did not evict the booking
+            assert_eq!(
+                engine.client(CLIENT).unwrap().unwrap().bookings().count(),
+                1
+            );
+        }
+
+        #[test]
+        fn keeps_all_bookings_without_a_window() {
+            let mut engine = PaymentEngine::default();
+            for tx in 1..10 {
+                assert!(engine
+                    .execute(Transaction::new(tx, CLIENT, Deposit(100)).with_timestamp(tx.into()))
+                    .is_ok());
+            }
+
+            assert!(engine.execute(Transaction::new(1, CLIENT, Dispute)).is_ok());
+        }
+    }
+
+    #[cfg(test)]
+    mod invariants {
+        use crate::asset::Asset;
+        use crate::balance::Amount;
+        use crate::client::{ClientId, TransactionId};
+        use crate::dispute_window::DisputeWindow;
+        use crate::invariants;
+        use crate::payment_engine::{PaymentEngine, Transaction, TransactionType};
+        use proptest::prelude::*;
+        use proptest::sample::Index;
+        use std::collections::HashMap;
+
+        /// few clients and transaction ids, so that transfers and duplicates hit existing ones
+        const CLIENTS: ClientId = 6;
+        const TRANSACTION_IDS: TransactionId = 100;
+
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Kind {
+            Deposit,
+            Withdrawal,
+            Transfer,
+            Dispute,
+            Resolve,
+            Chargeback,
+            Lock,
+            Unlock,
+            Close,
+        }
+
+        /// a generated transaction, which can be turned into a [Transaction] more than once
+        #[derive(Debug, Clone)]
+        struct Step {
+            kind: Kind,
+            tx: TransactionId,
+            client: ClientId,
+            to: ClientId,
+            amount: Amount,
+            asset: Asset,
+            /// picks a previous booking for disputes, resolves and chargebacks if given
+            booking: Option<Index>,
+            timestamp: Option<u64>,
+        }
+
+        impl Step {
+            /// refers to one of the given bookings, so that disputes mostly hit a booking
+            fn settle(mut self, bookings: &[(TransactionId, ClientId, Asset)]) -> Self {
+                let is_settlement =
+                    matches!(self.kind, Kind::Dispute | Kind::Resolve | Kind::Chargeback);
+                let booking = self
+                    .booking
+                    .filter(|_| is_settlement && !bookings.is_empty());
+                if let Some(index) = booking {
+                    (self.tx, self.client, self.asset) = *index.get(bookings);
+                }
+
+                self
+            }
+
+            fn transaction(&self) -> Transaction {
+                let transaction_type = match self.kind {
+                    Kind::Deposit => TransactionType::Deposit(self.amount),
+                    Kind::Withdrawal => TransactionType::Withdrawal(self.amount),
+                    Kind::Transfer => TransactionType::Transfer {
+                        to: self.to,
+                        amount: self.amount,
+                    },
+                    Kind::Dispute => TransactionType::Dispute,
+                    Kind::Resolve => TransactionType::Resolve,
+                    Kind::Chargeback => TransactionType::Chargeback,
+                    Kind::Lock => TransactionType::Lock,
+                    Kind::Unlock => TransactionType::Unlock,
+                    Kind::Close => TransactionType::Close,
+                };
+
+                let transaction =
+                    Transaction::new(self.tx, self.client, transaction_type).with_asset(self.asset);
+                match self.timestamp {
+                    Some(timestamp) => transaction.with_timestamp(timestamp),
+                    None => transaction,
+                }
+            }
+        }
+
+        fn step() -> impl Strategy<Value = Step> {
+            let kind = prop_oneof![
+                4 => Just(Kind::Deposit),
+                2 => Just(Kind::Withdrawal),
+                2 => Just(Kind::Transfer),
+                3 => Just(Kind::Dispute),
+                1 => Just(Kind::Resolve),
+                1 => Just(Kind::Chargeback),
+                1 => Just(Kind::Lock),
+                1 => Just(Kind::Unlock),
+                1 => Just(Kind::Close),
+            ];
+            // mostly small amounts, some close to the maximum to provoke overflows
+            let amount = prop_oneof![
+                9 => 0..=1_000_000 as Amount,
+                1 => Amount::MAX / 2..=Amount::MAX,
+            ];
+            let asset = prop_oneof![Just(Asset::default()), Just("USD".parse().unwrap())];
+
+            (
+                kind,
+                1..=TRANSACTION_IDS,
+                1..=CLIENTS,
+                1..=CLIENTS,
+                amount,
+                asset,
+                proptest::option::weighted(0.9, any::<Index>()),
+                // unordered timestamps, so that the clock of a window jumps ahead and back
+                proptest::option::of(0..1_000u64),
+            )
+                .prop_map(
+                    |(kind, tx, client, to, amount, asset, booking, timestamp)| Step {
+                        kind,
+                        tx,
+                        client,
+                        to,
+                        amount,
+                        asset,
+                        booking,
+                        timestamp,
+                    },
+                )
+        }
+
+        fn window() -> impl Strategy<Value = Option<DisputeWindow>> {
+            proptest::option::of(prop_oneof![
+                (1..20u64).prop_map(DisputeWindow::Transactions),
+                (1..200u64).prop_map(DisputeWindow::Age),
+            ])
+        }
+
+        /// the sum of all balances per asset as it follows from the executed transactions
+        ///
+        /// deposits add funds, withdrawals and chargebacks remove them and transfers only move
+        /// them. A disputed withdrawal (or the sending side of a transfer) holds its amount as
+        /// it might be owed back, until it is resolved or charged back.
+        #[derive(Default)]
+        struct Ledger {
+            bookings: HashMap<TransactionId, (Kind, Amount)>,
+            totals: HashMap<Asset, i128>,
+        }
+
+        impl Ledger {
+            fn executed(&mut self, step: &Step) {
+                let booking = self.bookings.get(&step.tx).copied();
+                let change = match (step.kind, booking) {
+                    (Kind::Deposit, _) => i128::from(step.amount),
+                    (Kind::Withdrawal, _) => -i128::from(step.amount),
+                    (Kind::Dispute, Some((Kind::Withdrawal | Kind::Transfer, amount))) => {
+                        i128::from(amount)
+                    }
+                    (Kind::Resolve, Some((Kind::Withdrawal | Kind::Transfer, amount))) => {
+                        -i128::from(amount)
+                    }
+                    (Kind::Chargeback, Some((Kind::Deposit | Kind::Transfer, amount))) => {
+                        -i128::from(amount)
+                    }
+                    _ => 0,
+                };
+                *self.totals.entry(step.asset).or_default() += change;
+
+                if matches!(step.kind, Kind::Deposit | Kind::Withdrawal | Kind::Transfer) {
+                    self.bookings.insert(step.tx, (step.kind, step.amount));
+                }
+            }
+        }
+
+        /// the sum of all balances per asset has to match the executed transactions
+        fn assert_totals(engine: &PaymentEngine, ledger: &Ledger) {
+            let totals = engine.totals().unwrap();
+            for (asset, total) in &ledger.totals {
+                let actual = totals.assets.get(asset).map_or(0, |totals| totals.total);
+                assert_eq!(i128::try_from(actual).unwrap(), *total);
+            }
+        }
+
+        proptest! {
+            #[test]
+            fn hold_after_every_transaction(
+                steps in prop::collection::vec(step(), 1..100),
+                window in window(),
+            ) {
+                let mut engine = match window {
+                    Some(window) => PaymentEngine::default().with_dispute_window(window),
+                    None => PaymentEngine::default(),
+                };
+                let mut ledger = Ledger::default();
+                // both sides of all executed deposits, withdrawals and transfers
+                let mut bookings = Vec::new();
+
+                for step in steps {
+                    let step = step.settle(&bookings);
+
+                    if invariants::execute(&mut engine, step.transaction()).is_ok() {
+                        ledger.executed(&step);
+                        match step.kind {
+                            Kind::Deposit | Kind::Withdrawal => {
+                                bookings.push((step.tx, step.client, step.asset))
+                            }
+                            Kind::Transfer => bookings.extend([
+                                (step.tx, step.client, step.asset),
+                                (step.tx, step.to, step.asset),
+                            ]),
+                            _ => {}
+                        }
+                    }
+
+                    assert_totals(&engine, &ledger);
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod store {
+        use crate::client::ExecutionError::StoreUnavailable;
+        use crate::client::{Client, ClientId};
+        use crate::payment_engine::TransactionType::{Chargeback, Deposit, Dispute, Transfer};
+        use crate::payment_engine::{PaymentEngine, Transaction};
+        use crate::store::{ClientStore, DiskStore};
+        use serde_json::Value;
+        use std::borrow::Cow;
+        use std::env::temp_dir;
+        use std::fs;
+        use std::io;
+        use std::iter;
+
+        /// a store that cannot load any client
+        struct FailingStore;
+
+        impl ClientStore for FailingStore {
+            fn get(&mut self, _: ClientId) -> io::Result<Option<&mut Client>> {
+                Err(io::Error::other("unavailable"))
+            }
+
+            fn get_pair(
+                &mut self,
+                _: ClientId,
+                _: Option<ClientId>,
+            ) -> io::Result<(Option<&mut Client>, Option<&mut Client>)> {
+                Err(io::Error::other("unavailable"))
+            }
+
+            fn put(&mut self, _: Client) {}
+
+            fn for_each<F: FnMut(&Client)>(&mut self, _: F) -> io::Result<()> {
+                Err(io::Error::other("unavailable"))
+            }
+
+            fn client(&self, _: ClientId) -> io::Result<Option<Cow<'_, Client>>> {
+                Err(io::Error::other("unavailable"))
+            }
+
+            fn clients(&self) -> impl Iterator<Item = io::Result<Cow<'_, Client>>> {
+                iter::once(Err(io::Error::other("unavailable")))
+            }
+
+            fn into_clients(self) -> io::Result<Vec<Client>> {
+                Err(io::Error::other("unavailable"))
+            }
+        }
+
+        fn transactions() -> Vec<Transaction> {
+            let mut transactions: Vec<Transaction> = (1..=4)
+                .map(|client| Transaction::new(client.into(), client, Deposit(100)))
+                .collect();
+            transactions.extend([
+                Transaction::new(5, 1, Transfer { to: 4, amount: 50 }),
+                Transaction::new(6, 3, Transfer { to: 2, amount: 20 }),
+                Transaction::new(5, 1, Dispute),
+                Transaction::new(2, 2, Dispute),
+                Transaction::new(5, 1, Chargeback),
+            ]);
+
+            transactions
+        }
+
+        #[test]
+        fn disk_store_matches_memory() {
+            let path = temp_dir().join(format!("payment_engine_disk_{}.jsonl", std::process::id()));
+            let mut memory = PaymentEngine::default();
+            let mut disk = PaymentEngine::with_store(DiskStore::create(&path, 2).unwrap());
+
+            for (transaction, expected) in transactions().into_iter().zip(transactions()) {
+                let expected = memory.execute(expected);
+                assert_eq!(disk.execute(transaction), expected);
+            }
+
+            // clients are compared by their serialized state including the booking history, as
+            // json objects are equal independent of the order of their keys
+            let mut clients = Vec::new();
+            disk.for_each_client(|client| clients.push(serde_json::to_value(client).unwrap()))
+                .unwrap();
+            let expected: Vec<Value> = memory
+                .into_clients()
+                .unwrap()
+                .iter()
+                .map(|client| serde_json::to_value(client).unwrap())
+                .collect();
+            assert_eq!(clients, expected);
+            fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn disk_store_answers_queries() {
+            let path = temp_dir().join(format!(
+                "payment_engine_queries_{}.jsonl",
+                std::process::id()
+            ));
+            let mut memory = PaymentEngine::default();
+            let mut disk = PaymentEngine::with_store(DiskStore::create(&path, 2).unwrap());
+            for (transaction, expected) in transactions().into_iter().zip(transactions()) {
+                let _ = memory.execute(expected);
+                let _ = disk.execute(transaction);
+            }
+
+            let value = |client: Cow<Client>| serde_json::to_value(&*client).unwrap();
+            for id in 1..=5 {
+                assert_eq!(
+                    disk.client(id).unwrap().map(value),
+                    memory.client(id).unwrap().map(value)
+                );
+            }
+            for tx in 1..=6 {
+                let booking = |(client, booking)| (client, serde_json::to_value(booking).unwrap());
+                assert_eq!(
+                    disk.booking(tx).unwrap().map(booking),
+                    memory.booking(tx).unwrap().map(booking)
+                );
+            }
+            assert_eq!(disk.totals().unwrap(), memory.totals().unwrap());
+            assert_eq!(disk.clients().count(), 4);
+
+            let ids = |clients: Vec<Client>| clients.iter().map(Client::id).collect::<Vec<_>>();
+            assert_eq!(
+                ids(disk.into_clients().unwrap()),
+                ids(memory.into_clients().unwrap())
+            );
+            fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn rejects_transactions_if_the_store_fails() {
+            let mut engine = PaymentEngine::with_store(FailingStore);
+
+            assert_eq!(
+                engine.execute(Transaction::new(1, 1, Deposit(100))),
+                Err(StoreUnavailable)
+            );
+            assert!(!engine.has_transaction(&1));
+            assert!(engine.client(1).is_err());
+            assert!(engine.totals().is_err());
+        }
+    }
+
     fn engine_with_client(id: ClientId, balance: Balance) -> PaymentEngine {
-        let mut clients = ClientMap::default();
+        let mut clients = MemoryStore::default();
         let mut client = Client::new(id);
-        *client.get_balance_mut() = balance;
+        *client.get_balance_mut(Asset::default()) = balance;
 
-        clients.insert(id, client);
+        clients.insert(client);
 
-        PaymentEngine { clients }
+        PaymentEngine::with_store(clients)
     }
 }
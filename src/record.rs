@@ -0,0 +1,465 @@
+use crate::asset::Asset;
+use crate::client::{AccountStatus, Client, ClientId, TransactionId};
+use crate::decimal::Decimal;
+use crate::payment_engine::{Transaction, TransactionType};
+use crate::record::ParseError::{
+    MissingAmount, MissingRecipient, UnexpectedAmount, UnexpectedRecipient, UnknownType,
+};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ParseError {
+    UnknownType(String),
+    MissingAmount,
+    UnexpectedAmount,
+    MissingRecipient,
+    UnexpectedRecipient,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnknownType(r#type) => write!(f, "unknown transaction type `{}`", r#type),
+            MissingAmount => write!(f, "transaction requires an amount"),
+            UnexpectedAmount => write!(f, "transaction must not provide an amount"),
+            MissingRecipient => write!(f, "transfer requires a `to_client`"),
+            UnexpectedRecipient => write!(f, "only transfers may provide a `to_client`"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug, Deserialize)]
+pub struct CsvTransactionItem {
+    r#type: String,
+    client: ClientId,
+    tx: TransactionId,
+    amount: Option<Decimal>,
+    /// the asset column is optional, rows without one are booked in the default asset
+    #[serde(default, alias = "currency")]
+    asset: Option<Asset>,
+    /// the receiving client of a transfer, the column is optional as well
+    #[serde(default)]
+    to_client: Option<ClientId>,
+    /// the time of the transaction, only required for an age based dispute window
+    #[serde(default)]
+    timestamp: Option<u64>,
+}
+
+impl TryFrom<CsvTransactionItem> for Transaction {
+    type Error = ParseError;
+
+    /// converts a raw csv row into a [Transaction]
+    ///
+    /// # Errors
+    /// - [UnknownType] if `type` is not one of the supported transaction types
+    /// - [MissingAmount] if a deposit, withdrawal or transfer does not provide an amount
+    /// - [UnexpectedAmount] if a dispute, resolve, chargeback or administrative transaction
+    ///   provides an amount
+    /// - [MissingRecipient] if a transfer does not provide a `to_client`
+    /// - [UnexpectedRecipient] if any other transaction provides a `to_client`
+    fn try_from(value: CsvTransactionItem) -> Result<Self, Self::Error> {
+        let to_client = value.to_client;
+        if to_client.is_some() && value.r#type != "transfer" {
+            return Err(UnexpectedRecipient);
+        }
+
+        let transaction_type = match (value.r#type.as_str(), value.amount) {
+            ("deposit", Some(amount)) => TransactionType::Deposit(amount.minor_units()),
+            ("withdrawal", Some(amount)) => TransactionType::Withdrawal(amount.minor_units()),
+            ("transfer", Some(amount)) => TransactionType::Transfer {
+                to: to_client.ok_or(MissingRecipient)?,
+                amount: amount.minor_units(),
+            },
+            ("deposit" | "withdrawal" | "transfer", None) => return Err(MissingAmount),
+            ("dispute", None) => TransactionType::Dispute,
+            ("resolve", None) => TransactionType::Resolve,
+            ("chargeback", None) => TransactionType::Chargeback,
+            ("lock", None) => TransactionType::Lock,
+            ("unlock", None) => TransactionType::Unlock,
+            ("close", None) => TransactionType::Close,
+            ("dispute" | "resolve" | "chargeback" | "lock" | "unlock" | "close", Some(_)) => {
+                return Err(UnexpectedAmount)
+            }
+            (other, _) => return Err(UnknownType(other.to_string())),
+        };
+
+        let transaction = Transaction::new(value.tx, value.client, transaction_type)
+            .with_asset(value.asset.unwrap_or_default());
+
+        Ok(match value.timestamp {
+            Some(timestamp) => transaction.with_timestamp(timestamp),
+            None => transaction,
+        })
+    }
+}
+
+/// the shape of the output rows
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ClientFormat {
+    /// adds the asset column
+    pub with_asset: bool,
+    /// writes amounts with all [PRECISION](crate::PRECISION) decimal places (e.g. `1.5000` instead of `1.5`)
+    pub fixed_decimals: bool,
+}
+
+/// an amount of the output, optionally with all decimal places
+#[derive(Debug)]
+struct OutputDecimal {
+    value: Decimal,
+    fixed: bool,
+}
+
+impl Display for OutputDecimal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.fixed {
+            true => self.value.fixed().fmt(f),
+            false => self.value.fmt(f),
+        }
+    }
+}
+
+impl Serialize for OutputDecimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// a column of the output rows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Client,
+    Asset,
+    Available,
+    Held,
+    Total,
+    Locked,
+    Status,
+    LockReason,
+}
+
+impl Column {
+    /// all columns in the order they are written by default
+    pub const ALL: [Column; 8] = [
+        Column::Client,
+        Column::Asset,
+        Column::Available,
+        Column::Held,
+        Column::Total,
+        Column::Locked,
+        Column::Status,
+        Column::LockReason,
+    ];
+
+    /// the column with the given name, see [Column::name]
+    pub fn from_name(name: &str) -> Option<Column> {
+        Column::ALL.into_iter().find(|column| column.name() == name)
+    }
+
+    /// the name of the column in the header or the key of a json line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Client => "client",
+            Column::Asset => "asset",
+            Column::Available => "available",
+            Column::Held => "held",
+            Column::Total => "total",
+            Column::Locked => "locked",
+            Column::Status => "status",
+            Column::LockReason => "lock_reason",
+        }
+    }
+
+    /// whether the column reports the account status, which is only written on request
+    pub fn is_status(&self) -> bool {
+        matches!(self, Column::Status | Column::LockReason)
+    }
+}
+
+/// a single row of the output, there is one row for every asset a client holds
+#[derive(Debug, Serialize)]
+pub struct CsvClientItem {
+    client: ClientId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    asset: Option<Asset>,
+    available: OutputDecimal,
+    held: OutputDecimal,
+    total: OutputDecimal,
+    locked: bool,
+    status: &'static str,
+    /// only set for locked clients
+    lock_reason: Option<&'static str>,
+}
+
+impl CsvClientItem {
+    /// converts a client into one row per asset, ordered by asset
+    ///
+    /// the asset column is only written if the format asks for it, so that the output of single
+    /// asset inputs keeps its original shape.
+    pub fn rows(client: &Client, format: ClientFormat) -> Vec<Self> {
+        let amount = |value| OutputDecimal {
+            value: Decimal::new(value),
+            fixed: format.fixed_decimals,
+        };
+
+        client
+            .balances()
+            .map(|(asset, balance)| {
+                let available = balance.available();
+                let frozen = balance.frozen();
+
+                // this should be safe as the engine keeps every total in range of a u64.
+                let total = available + frozen;
+                Self {
+                    client: client.id(),
+                    asset: format.with_asset.then_some(*asset),
+                    available: amount(available),
+                    held: amount(frozen),
+                    total: amount(total),
+                    locked: client.is_locked(),
+                    status: client.status().as_str(),
+                    lock_reason: match client.status() {
+                        AccountStatus::Locked(reason) => Some(reason.as_str()),
+                        _ => None,
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// the value of the given column, [Value::Null] for an empty field
+    pub fn field(&self, column: Column) -> Value {
+        match column {
+            Column::Client => Value::from(self.client),
+            Column::Asset => self
+                .asset
+                .map_or(Value::Null, |asset| Value::from(asset.as_str())),
+            Column::Available => Value::from(self.available.to_string()),
+            Column::Held => Value::from(self.held.to_string()),
+            Column::Total => Value::from(self.total.to_string()),
+            Column::Locked => Value::from(self.locked),
+            Column::Status => Value::from(self.status),
+            Column::LockReason => self.lock_reason.map_or(Value::Null, Value::from),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::Asset;
+    use crate::decimal::Decimal;
+    use crate::payment_engine::TransactionType::{
+        Deposit, Dispute, Lock, Transfer, Unlock, Withdrawal,
+    };
+    use crate::payment_engine::{PaymentEngine, Transaction, TransactionType};
+    use crate::record::ParseError::{
+        MissingAmount, MissingRecipient, UnexpectedAmount, UnexpectedRecipient, UnknownType,
+    };
+    use crate::record::{ClientFormat, Column, CsvClientItem, CsvTransactionItem, ParseError};
+    use serde_json::{json, Value};
+
+    fn parse(r#type: &str, amount: Option<Decimal>) -> Result<Transaction, ParseError> {
+        parse_transfer(r#type, amount, None)
+    }
+
+    fn parse_transfer(
+        r#type: &str,
+        amount: Option<Decimal>,
+        to_client: Option<u16>,
+    ) -> Result<Transaction, ParseError> {
+        Transaction::try_from(CsvTransactionItem {
+            r#type: r#type.to_string(),
+            client: 1,
+            tx: 2,
+            amount,
+            asset: None,
+            to_client,
+            timestamp: None,
+        })
+    }
+
+    fn expected(transaction_type: TransactionType) -> Result<Transaction, ParseError> {
+        Ok(Transaction::new(2, 1, transaction_type))
+    }
+
+    #[test]
+    fn converts_valid_items() {
+        let amount = Some(Decimal::new(15000));
+
+        assert_eq!(parse("deposit", amount), expected(Deposit(15000)));
+        assert_eq!(parse("withdrawal", amount), expected(Withdrawal(15000)));
+        assert_eq!(parse("dispute", None), expected(Dispute));
+        assert_eq!(parse("unlock", None), expected(Unlock));
+    }
+
+    #[test]
+    fn converts_transfers() {
+        let amount = Some(Decimal::new(15000));
+
+        assert_eq!(
+            parse_transfer("transfer", amount, Some(3)),
+            expected(Transfer {
+                to: 3,
+                amount: 15000
+            })
+        );
+        assert_eq!(
+            parse_transfer("transfer", amount, None),
+            Err(MissingRecipient)
+        );
+        assert_eq!(
+            parse_transfer("transfer", None, Some(3)),
+            Err(MissingAmount)
+        );
+        assert_eq!(
+            parse_transfer("deposit", amount, Some(3)),
+            Err(UnexpectedRecipient)
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_type() {
+        assert_eq!(
+            parse("refund", None),
+            Err(UnknownType("refund".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_missing_amount() {
+        assert_eq!(parse("deposit", None), Err(MissingAmount));
+        assert_eq!(parse("withdrawal", None), Err(MissingAmount));
+    }
+
+    #[test]
+    fn rejects_unexpected_amount() {
+        let amount = Some(Decimal::new(1));
+
+        assert_eq!(parse("dispute", amount), Err(UnexpectedAmount));
+        assert_eq!(parse("resolve", amount), Err(UnexpectedAmount));
+        assert_eq!(parse("chargeback", amount), Err(UnexpectedAmount));
+        assert_eq!(parse("close", amount), Err(UnexpectedAmount));
+    }
+
+    #[test]
+    fn reads_optional_asset_column() {
+        let parse_csv = |input: &str| -> Vec<Transaction> {
+            csv::Reader::from_reader(input.as_bytes())
+                .deserialize::<CsvTransactionItem>()
+                .map(|item| Transaction::try_from(item.unwrap()).unwrap())
+                .collect()
+        };
+        let usd: Asset = "USD".parse().unwrap();
+
+        assert_eq!(
+            parse_csv("type,client,tx,amount\ndeposit,1,2,1.5\n"),
+            vec![Transaction::new(2, 1, Deposit(15000))]
+        );
+        assert_eq!(
+            parse_csv("type,client,tx,amount,currency\ndeposit,1,2,1.5,USD\ndispute,1,2,,\n"),
+            vec![
+                Transaction::new(2, 1, Deposit(15000)).with_asset(usd),
+                Transaction::new(2, 1, Dispute),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_optional_timestamp_column() {
+        let transactions: Vec<Transaction> = csv::Reader::from_reader(
+            "type,client,tx,amount,timestamp\ndeposit,1,2,1.5,60\ndispute,1,2,,\n".as_bytes(),
+        )
+        .deserialize::<CsvTransactionItem>()
+        .map(|item| Transaction::try_from(item.unwrap()).unwrap())
+        .collect();
+
+        assert_eq!(
+            transactions,
+            vec![
+                Transaction::new(2, 1, Deposit(15000)).with_timestamp(60),
+                Transaction::new(2, 1, Dispute),
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_one_row_per_asset() {
+        let mut engine = PaymentEngine::default();
+        let usd: Asset = "USD".parse().unwrap();
+        engine
+            .execute(Transaction::new(1, 1, Deposit(15000)).with_asset(usd))
+            .unwrap();
+        engine.execute(Transaction::new(2, 1, Deposit(1))).unwrap();
+        let client = engine.into_clients().unwrap().pop().unwrap();
+
+        let write = |format: ClientFormat| -> String {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for item in CsvClientItem::rows(&client, format) {
+                writer.serialize(item).unwrap();
+            }
+            String::from_utf8(writer.into_inner().unwrap()).unwrap()
+        };
+
+        assert_eq!(
+            write(ClientFormat::default()),
+            "client,available,held,total,locked,status,lock_reason\n\
+             1,0.0001,0.0,0.0001,false,active,\n\
+             1,1.5,0.0,1.5,false,active,\n"
+        );
+        assert_eq!(
+            write(ClientFormat {
+                with_asset: true,
+                fixed_decimals: true
+            }),
+            "client,asset,available,held,total,locked,status,lock_reason\n\
+             1,,0.0001,0.0000,0.0001,false,active,\n\
+             1,USD,1.5000,0.0000,1.5000,false,active,\n"
+        );
+    }
+
+    #[test]
+    fn returns_fields_by_column() {
+        let mut engine = PaymentEngine::default();
+        engine
+            .execute(Transaction::new(1, 1, Deposit(15000)))
+            .unwrap();
+        engine.execute(Transaction::new(0, 1, Lock)).unwrap();
+        let client = engine.into_clients().unwrap().pop().unwrap();
+        let format = ClientFormat {
+            with_asset: true,
+            fixed_decimals: true,
+        };
+        let item = CsvClientItem::rows(&client, format).pop().unwrap();
+
+        let fields: Vec<Value> = Column::ALL
+            .iter()
+            .map(|column| item.field(*column))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                json!(1),
+                json!(""),
+                json!("1.5000"),
+                json!("0.0000"),
+                json!("1.5000"),
+                json!(true),
+                json!("locked"),
+                json!("manual"),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_columns_by_name() {
+        for column in Column::ALL {
+            assert_eq!(Column::from_name(column.name()), Some(column));
+        }
+        assert_eq!(Column::from_name("balance"), None);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::row::Record;
+use booking_engine::{ExecutionError, ParseError};
+use csv::{ErrorKind, StringRecord, Writer, WriterBuilder};
+use log::warn;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Seek, SeekFrom};
+use std::path::Path;
+
+/// the reason why an input row has not been applied to the engine
+#[derive(Debug)]
+pub enum Rejection {
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Io(io::Error),
+    Parse(ParseError),
+    Execution(ExecutionError),
+}
+
+impl From<csv::Error> for Rejection {
+    fn from(value: csv::Error) -> Self {
+        Rejection::Csv(value)
+    }
+}
+
+impl From<serde_json::Error> for Rejection {
+    fn from(value: serde_json::Error) -> Self {
+        Rejection::Json(value)
+    }
+}
+
+impl From<ParseError> for Rejection {
+    fn from(value: ParseError) -> Self {
+        Rejection::Parse(value)
+    }
+}
+
+impl From<ExecutionError> for Rejection {
+    fn from(value: ExecutionError) -> Self {
+        Rejection::Execution(value)
+    }
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // the position is already part of the report, so only keep the underlying message
+            Rejection::Csv(e) => match e.kind() {
+                ErrorKind::Deserialize { err, .. } => write!(f, "{}", err),
+                _ => write!(f, "{}", e),
+            },
+            Rejection::Json(e) => write!(f, "{}", e),
+            Rejection::Io(e) => write!(f, "{}", e),
+            Rejection::Parse(e) => write!(f, "{}", e),
+            Rejection::Execution(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// a single row of the rejected transactions report
+///
+/// keeps the original fields as they were provided so that the row can be fed back
+/// into an exception-handling queue.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CsvRejectItem {
+    /// only reported if there is more than one input
+    #[serde(skip_serializing_if = "Option::is_none")]
+    input: Option<String>,
+    line: u64,
+    r#type: String,
+    client: String,
+    tx: String,
+    amount: String,
+    /// only reported if the input has an asset column or if inputs are named
+    #[serde(skip_serializing_if = "Option::is_none")]
+    asset: Option<String>,
+    /// only reported if the input has a `to_client` column or if inputs are named
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to_client: Option<String>,
+    /// only reported if the input has a `timestamp` column or if inputs are named
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
+    reason: String,
+}
+
+impl CsvRejectItem {
+    /// collects the original fields of a row by the headers of its input
+    ///
+    /// if the `input` is named, the optional columns are always reported (empty if missing) so
+    /// that inputs with different headers yield rows of the same shape.
+    pub fn new(
+        input: Option<&str>,
+        line: u64,
+        record: Option<&StringRecord>,
+        headers: &StringRecord,
+        rejection: &Rejection,
+    ) -> Self {
+        let column = |names: &[&str]| headers.iter().position(|header| names.contains(&header));
+        let value = |index: Option<usize>| {
+            index
+                .and_then(|index| record.and_then(|r| r.get(index)))
+                .unwrap_or_default()
+                .to_string()
+        };
+        let field = |name: &str| value(column(&[name]));
+        let optional_field = |names: &[&str]| match input {
+            Some(_) => Some(value(column(names))),
+            None => column(names).map(|index| value(Some(index))),
+        };
+
+        Self {
+            input: input.map(str::to_string),
+            line,
+            r#type: field("type"),
+            client: field("client"),
+            tx: field("tx"),
+            amount: field("amount"),
+            asset: optional_field(&["asset", "currency"]),
+            to_client: optional_field(&["to_client"]),
+            timestamp: optional_field(&["timestamp"]),
+            reason: rejection.to_string(),
+        }
+    }
+}
+
+/// logs rejected rows and optionally writes them to a report file
+pub struct RejectWriter {
+    /// the input currently processed, only set if inputs are named
+    input: Option<String>,
+    is_naming_inputs: bool,
+    headers: StringRecord,
+    writer: Option<Writer<File>>,
+}
+
+impl RejectWriter {
+    pub fn new(path: Option<&Path>) -> io::Result<Self> {
+        Self::resume(path, 0)
+    }
+
+    /// continues a previously written report
+    ///
+    /// everything after the given offset (rows written after the last checkpoint) is discarded.
+    pub fn resume(path: Option<&Path>, offset: u64) -> io::Result<Self> {
+        let writer = path
+            .map(|path| {
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(offset == 0)
+                    .open(path)?;
+                // a new report is already truncated, which also works for devices
+                if offset > 0 {
+                    file.set_len(offset)?;
+                    file.seek(SeekFrom::End(0))?;
+                }
+
+                // the header is only written along with the first row
+                Ok::<_, io::Error>(
+                    WriterBuilder::new()
+                        .has_headers(offset == 0)
+                        .from_writer(file),
+                )
+            })
+            .transpose()?;
+
+        Ok(Self {
+            input: None,
+            is_naming_inputs: false,
+            headers: StringRecord::new(),
+            writer,
+        })
+    }
+
+    /// adds an `input` column that names the input of each row
+    pub fn with_input_column(mut self) -> Self {
+        self.is_naming_inputs = true;
+        self
+    }
+
+    /// all following rows belong to the given input, their fields are looked up by its headers
+    pub fn start_input(&mut self, input: &str, headers: StringRecord) {
+        self.input = self.is_naming_inputs.then(|| input.to_string());
+        self.headers = headers;
+    }
+
+    pub fn reject(
+        &mut self,
+        line: u64,
+        record: Option<&Record>,
+        rejection: &Rejection,
+    ) -> io::Result<()> {
+        match self.input.as_ref() {
+            Some(input) => warn!("rejected {} line {}: {}", input, line, rejection),
+            None => warn!("rejected line {}: {}", line, rejection),
+        }
+
+        if let Some(writer) = self.writer.as_mut() {
+            let input = self.input.as_deref();
+            let fields = record.and_then(Record::fields);
+            let item = CsvRejectItem::new(input, line, fields.as_deref(), &self.headers, rejection);
+            writer.serialize(item)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// flushes the report and returns its current length
+    pub fn offset(&mut self) -> io::Result<u64> {
+        self.flush()?;
+
+        match self.writer.as_ref() {
+            Some(writer) => Ok(writer.get_ref().metadata()?.len()),
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reject::{CsvRejectItem, Rejection};
+    use booking_engine::ExecutionError::ClientDoesNotExist;
+    use booking_engine::ParseError::UnknownType;
+    use csv::StringRecord;
+
+    #[test]
+    fn keeps_original_fields_by_header() {
+        let headers = StringRecord::from(vec!["client", "type", "tx", "amount"]);
+        let record = StringRecord::from(vec!["1", "withdrawal", "5", ""]);
+        let item = CsvRejectItem::new(
+            None,
+            3,
+            Some(&record),
+            &headers,
+            &Rejection::Execution(ClientDoesNotExist),
+        );
+
+        assert_eq!(
+            item,
+            CsvRejectItem {
+                input: None,
+                line: 3,
+                r#type: "withdrawal".to_string(),
+                client: "1".to_string(),
+                tx: "5".to_string(),
+                amount: "".to_string(),
+                asset: None,
+                to_client: None,
+                timestamp: None,
+                reason: "client does not exist".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn missing_record_leaves_fields_empty() {
+        let headers = StringRecord::from(vec!["type", "client", "tx", "amount"]);
+        let item = CsvRejectItem::new(
+            None,
+            7,
+            None,
+            &headers,
+            &Rejection::Parse(UnknownType("foo".to_string())),
+        );
+
+        assert_eq!(item.line, 7);
+        assert_eq!(item.r#type, "");
+        assert_eq!(item.reason, "unknown transaction type `foo`");
+    }
+
+    #[test]
+    fn keeps_asset_if_present() {
+        let headers = StringRecord::from(vec!["type", "client", "tx", "amount", "currency"]);
+        let record = StringRecord::from(vec!["dispute", "1", "5", "", "USD"]);
+        let item = CsvRejectItem::new(
+            None,
+            2,
+            Some(&record),
+            &headers,
+            &Rejection::Execution(ClientDoesNotExist),
+        );
+
+        assert_eq!(item.asset, Some("USD".to_string()));
+    }
+
+    #[test]
+    fn names_input_with_all_optional_columns() {
+        let headers = StringRecord::from(vec!["type", "client", "tx", "amount", "to_client"]);
+        let record = StringRecord::from(vec!["transfer", "1", "5", "1.0", "2"]);
+        let item = CsvRejectItem::new(
+            Some("b.csv"),
+            4,
+            Some(&record),
+            &headers,
+            &Rejection::Execution(ClientDoesNotExist),
+        );
+
+        assert_eq!(item.input, Some("b.csv".to_string()));
+        assert_eq!(item.asset, Some("".to_string()));
+        assert_eq!(item.to_client, Some("2".to_string()));
+        assert_eq!(item.timestamp, Some("".to_string()));
+    }
+
+    #[test]
+    fn keeps_timestamp_if_present() {
+        let headers = StringRecord::from(vec!["type", "client", "tx", "amount", "timestamp"]);
+        let record = StringRecord::from(vec!["dispute", "1", "5", "", "1060"]);
+        let item = CsvRejectItem::new(
+            None,
+            3,
+            Some(&record),
+            &headers,
+            &Rejection::Execution(ClientDoesNotExist),
+        );
+
+        assert_eq!(item.timestamp, Some("1060".to_string()));
+        assert_eq!(item.to_client, None);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::jsonl;
+use crate::reject::Rejection;
+use booking_engine::{CsvTransactionItem, Transaction};
+use csv::{Position, Reader, StringRecord};
+use std::borrow::Cow;
+use std::io::Read;
+use std::iter;
+
+/// the original content of an input row
+///
+/// a json line is only split into its fields once it is written to the rejects report, as this
+/// requires to parse it a second time.
+pub enum Record {
+    Csv(StringRecord),
+    Json { text: String, line: u64 },
+}
+
+impl Record {
+    /// the fields of the row, a json line that is not an object has none
+    pub fn fields(&self) -> Option<Cow<'_, StringRecord>> {
+        match self {
+            Record::Csv(record) => Some(Cow::Borrowed(record)),
+            Record::Json { text, line } => jsonl::record(text, *line).map(Cow::Owned),
+        }
+    }
+}
+
+/// a single input row, the record keeps its original content for the rejects report
+pub struct Row {
+    pub line: u64,
+    pub record: Option<Record>,
+    pub transaction: Result<Transaction, Rejection>,
+    /// the position in the raw input right after the row, only known for csv
+    pub end: Option<Position>,
+}
+
+/// parses a single input row into a transaction
+fn parse(record: &StringRecord, headers: &StringRecord) -> Result<Transaction, Rejection> {
+    let item: CsvTransactionItem = record.deserialize(Some(headers))?;
+
+    Ok(Transaction::try_from(item)?)
+}
+
+fn line_of(record: &StringRecord) -> u64 {
+    record.position().map(|p| p.line()).unwrap_or_default()
+}
+
+/// reads all rows of a csv
+///
+/// an unreadable rest of the input (e.g. a corrupt archive) ends the rows with its error.
+pub fn csv_rows<R: Read>(
+    csv_reader: Reader<R>,
+    headers: StringRecord,
+) -> impl Iterator<Item = Row> {
+    let mut records = csv_reader.into_records();
+    let mut last_line = 0;
+    let mut is_readable = true;
+
+    iter::from_fn(move || {
+        if !is_readable {
+            return None;
+        }
+
+        let row = match records.next()? {
+            Ok(record) => {
+                last_line = line_of(&record);
+                Row {
+                    line: last_line,
+                    transaction: parse(&record, &headers),
+                    record: Some(Record::Csv(record)),
+                    end: Some(records.reader().position().clone()),
+                }
+            }
+            Err(e) => {
+                is_readable = !e.is_io_error();
+                Row {
+                    line: e.position().map(|p| p.line()).unwrap_or(last_line),
+                    record: None,
+                    transaction: Err(Rejection::Csv(e)),
+                    end: Some(records.reader().position().clone()),
+                }
+            }
+        };
+
+        Some(row)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reject::Rejection;
+    use crate::row::csv_rows;
+    use booking_engine::Transaction;
+    use booking_engine::TransactionType::Deposit;
+    use csv::{ReaderBuilder, Trim};
+
+    #[test]
+    fn reads_rows_with_their_end_position() {
+        let input = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1\ndeposit,2,2,2.0\n";
+        let mut csv_reader = ReaderBuilder::new()
+            .trim(Trim::All)
+            .from_reader(input.as_bytes());
+        let headers = csv_reader.headers().unwrap().clone();
+        let rows: Vec<_> = csv_rows(csv_reader, headers).collect();
+
+        assert_eq!(
+            rows.iter().map(|row| row.line).collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(
+            rows[0].transaction.as_ref().unwrap(),
+            &Transaction::new(1, 1, Deposit(10000))
+        );
+        assert!(matches!(rows[1].transaction, Err(Rejection::Csv(_))));
+        assert_eq!(
+            rows.iter()
+                .map(|row| row.end.as_ref().unwrap().byte())
+                .collect::<Vec<_>>(),
+            vec![38, 48, 64]
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use crate::client::ExecutionError::DuplicateTransaction;
+use crate::client::{Client, ClientId, ExecutionError, TransactionId};
+use crate::payment_engine::{PaymentEngine, Transaction, TransactionType};
+use std::collections::HashMap;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+use std::thread::JoinHandle;
+
+/// number of transactions that can be queued per shard before the producer blocks
+const QUEUE_CAPACITY: usize = 1024;
+
+/// a failed transaction, tagged with the position it has been submitted at
+type Failure<T> = (u64, T, ExecutionError);
+
+enum Message<T> {
+    Execute(u64, Transaction, T),
+    /// asks a shard whether it has executed a transaction with the given id
+    Query(TransactionId, SyncSender<bool>),
+    /// removes a client from its shard so that it can be lent to another one
+    Take(ClientId, SyncSender<Option<Client>>),
+    /// executes a transaction with a lent client and hands it back along with the outcome
+    ExecuteWith {
+        sequence: u64,
+        transaction: Transaction,
+        tag: T,
+        guest: (ClientId, Option<Client>),
+        reply: SyncSender<(Option<Client>, bool)>,
+    },
+    /// returns a lent client to its shard
+    Put(Client),
+}
+
+struct Shard<T> {
+    sender: SyncSender<Message<T>>,
+    worker: JoinHandle<(PaymentEngine, Vec<Failure<T>>)>,
+}
+
+/// the final state of a [ShardedPaymentEngine]
+pub struct ShardedResult<T> {
+    pub clients: Vec<Client>,
+    /// all failed transactions in the order they have been submitted
+    pub failures: Vec<(T, ExecutionError)>,
+}
+
+/// a [PaymentEngine] that processes clients in parallel
+///
+/// transactions are routed by their client to one of N shards, each owning its own
+/// [PaymentEngine] on a dedicated worker thread. As every client is always handled by the same
+/// shard, the per-client ordering is preserved.
+///
+/// Transaction ids are unique across all clients, so the engine keeps track of which shards have
+/// seen a deposit, withdrawal or transfer id. If an id shows up for a different shard, the
+/// previous shards are queried (in order with their queue) before the transaction is forwarded,
+/// which yields the same result as a single [PaymentEngine].
+///
+/// A transfer (or a dispute of one) between clients of different shards is executed by lending
+/// the counterparty to the shard of the transaction's client for the duration of the transaction.
+/// Both shards are drained up to that point, so such transfers are a synchronization point.
+///
+/// Every transaction carries a tag of type `T` (e.g. the input line) that is handed back for
+/// failed transactions.
+pub struct ShardedPaymentEngine<T: Send + 'static = ()> {
+    shards: Vec<Shard<T>>,
+    seen: HashMap<TransactionId, Vec<usize>>,
+    /// the clients of every successful transfer across shards
+    transfers: HashMap<TransactionId, (ClientId, ClientId)>,
+    sequence: u64,
+    failures: Vec<Failure<T>>,
+}
+
+impl<T: Send + 'static> ShardedPaymentEngine<T> {
+    /// spawns a worker thread for each of the given number of shards
+    pub fn new(shards: usize) -> Self {
+        let shards = (0..shards.max(1))
+            .map(|_| {
+                let (sender, receiver) = sync_channel(QUEUE_CAPACITY);
+                let worker = thread::spawn(move || Self::run(receiver));
+                Shard { sender, worker }
+            })
+            .collect();
+
+        Self {
+            shards,
+            seen: HashMap::default(),
+            transfers: HashMap::default(),
+            sequence: 0,
+            failures: Vec::default(),
+        }
+    }
+
+    /// queues a given Transaction on the shard owning its client
+    ///
+    /// the transaction is executed asynchronously; failures are collected and returned by
+    /// [ShardedPaymentEngine::finish].
+    pub fn execute(&mut self, transaction: Transaction, tag: T) {
+        let sequence = self.sequence;
+        self.sequence += 1;
+
+        let shard = self.shard_of(transaction.client());
+
+        if transaction.is_new_transaction() {
+            let seen = self.seen.entry(transaction.id()).or_default();
+
+            // ids are only registered on success, so ask every other shard that has seen the id
+            let is_duplicate = seen
+                .iter()
+                .filter(|&&other| other != shard)
+                .any(|&other| Self::query(&self.shards[other], transaction.id()));
+            if is_duplicate {
+                self.failures.push((sequence, tag, DuplicateTransaction));
+                return;
+            }
+
+            if !seen.contains(&shard) {
+                seen.push(shard);
+            }
+        }
+
+        match self.counterparty_of(&transaction) {
+            Some(other) if self.shard_of(other) != shard => {
+                self.execute_with(shard, other, sequence, transaction, tag)
+            }
+            _ => Self::send(
+                &self.shards[shard],
+                Message::Execute(sequence, transaction, tag),
+            ),
+        }
+    }
+
+    fn shard_of(&self, client: ClientId) -> usize {
+        client as usize % self.shards.len()
+    }
+
+    /// the other client a transfer or a dispute of a transfer across shards books on
+    fn counterparty_of(&self, transaction: &Transaction) -> Option<ClientId> {
+        match *transaction.transaction_type() {
+            TransactionType::Transfer { to, .. } => Some(to),
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+                let (from, to) = *self.transfers.get(&transaction.id())?;
+                match transaction.client() {
+                    client if client == from => Some(to),
+                    client if client == to => Some(from),
+                    _ => None,
+                }
+            }
+            TransactionType::Deposit(_)
+            | TransactionType::Withdrawal(_)
+            | TransactionType::Lock
+            | TransactionType::Unlock
+            | TransactionType::Close => None,
+        }
+    }
+
+    /// executes a transaction on the given shard with the counterparty lent from its own shard
+    fn execute_with(
+        &mut self,
+        shard: usize,
+        counterparty: ClientId,
+        sequence: u64,
+        transaction: Transaction,
+        tag: T,
+    ) {
+        let home = &self.shards[self.shard_of(counterparty)];
+        let (sender, receiver) = sync_channel(1);
+        Self::send(home, Message::Take(counterparty, sender));
+        let guest = receiver
+            .recv()
+            .expect("shard worker terminated unexpectedly");
+
+        let is_transfer = matches!(
+            transaction.transaction_type(),
+            TransactionType::Transfer { .. }
+        );
+        let parties = (transaction.client(), counterparty);
+        let id = transaction.id();
+
+        let (sender, receiver) = sync_channel(1);
+        Self::send(
+            &self.shards[shard],
+            Message::ExecuteWith {
+                sequence,
+                transaction,
+                tag,
+                guest: (counterparty, guest),
+                reply: sender,
+            },
+        );
+        let (guest, executed) = receiver
+            .recv()
+            .expect("shard worker terminated unexpectedly");
+
+        // the receiver of a transfer might have been created by it
+        if let Some(guest) = guest {
+            Self::send(home, Message::Put(guest));
+        }
+
+        if is_transfer && executed {
+            self.transfers.insert(id, parties);
+        }
+    }
+
+    fn send(shard: &Shard<T>, message: Message<T>) {
+        shard
+            .sender
+            .send(message)
+            .expect("shard worker terminated unexpectedly");
+    }
+
+    /// waits for all shards to finish and merges their clients ordered by their id
+    pub fn finish(self) -> ShardedResult<T> {
+        let mut clients = Vec::default();
+        let mut failures = self.failures;
+
+        for shard in self.shards {
+            // closing the channel stops the worker once its queue is drained
+            drop(shard.sender);
+
+            let (engine, shard_failures) = shard.worker.join().expect("shard worker panicked");
+            clients.extend(
+                engine
+                    .into_clients()
+                    .expect("clients in memory are always readable"),
+            );
+            failures.extend(shard_failures);
+        }
+
+        clients.sort_unstable_by_key(Client::id);
+        failures.sort_by_key(|(sequence, _, _)| *sequence);
+
+        ShardedResult {
+            clients,
+            failures: failures.into_iter().map(|(_, tag, e)| (tag, e)).collect(),
+        }
+    }
+
+    fn query(shard: &Shard<T>, tx: TransactionId) -> bool {
+        let (sender, receiver) = sync_channel(1);
+        Self::send(shard, Message::Query(tx, sender));
+
+        receiver
+            .recv()
+            .expect("shard worker terminated unexpectedly")
+    }
+
+    fn run(receiver: Receiver<Message<T>>) -> (PaymentEngine, Vec<Failure<T>>) {
+        let mut engine = PaymentEngine::default();
+        let mut failures = Vec::default();
+
+        for message in receiver {
+            match message {
+                Message::Execute(sequence, transaction, tag) => {
+                    if let Err(e) = engine.execute(transaction) {
+                        failures.push((sequence, tag, e));
+                    }
+                }
+                Message::Query(tx, reply) => {
+                    // the requester is blocked on the reply, so it cannot have gone away
+                    let _ = reply.send(engine.has_transaction(&tx));
+                }
+                Message::Take(client, reply) => {
+                    let _ = reply.send(engine.take_client(client));
+                }
+                Message::ExecuteWith {
+                    sequence,
+                    transaction,
+                    tag,
+                    guest: (id, guest),
+                    reply,
+                } => {
+                    if let Some(guest) = guest {
+                        engine.insert_client(guest);
+                    }
+
+                    let executed = match engine.execute(transaction) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            failures.push((sequence, tag, e));
+                            false
+                        }
+                    };
+
+                    let _ = reply.send((engine.take_client(id), executed));
+                }
+                Message::Put(client) => engine.insert_client(client),
+            }
+        }
+
+        (engine, failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{ClientId, ExecutionError, TransactionId};
+    use crate::payment_engine::TransactionType::{
+        Chargeback, Deposit, Dispute, Resolve, Transfer, Withdrawal,
+    };
+    use crate::payment_engine::{PaymentEngine, Transaction, TransactionType};
+    use crate::sharded_engine::ShardedPaymentEngine;
+    use crate::test_support::snapshot;
+
+    fn transactions() -> Vec<Transaction> {
+        let mut transactions = Vec::new();
+        let mut add = |id: TransactionId, client: ClientId, transaction_type: TransactionType| {
+            transactions.push(Transaction::new(id, client, transaction_type))
+        };
+
+        for client in 0..20 {
+            let base = client as TransactionId * 10;
+            add(base, client, Deposit(1000));
+            add(base + 1, client, Withdrawal(300));
+            add(base + 2, client, Deposit(500));
+            add(base + 2, client, Deposit(500));
+            add(base + 3, client, Withdrawal(5000));
+            add(base + 2, client, Dispute);
+            if client % 2 == 0 {
+                add(base + 2, client, Resolve);
+            } else {
+                add(base + 2, client, Chargeback);
+            }
+            add(base + 1, client, Dispute);
+            add(base + 4, client, Deposit(10));
+        }
+
+        // transfers within and across shards as well as to new clients
+        add(1000, 2, Transfer { to: 3, amount: 100 });
+        add(1001, 4, Transfer { to: 6, amount: 100 });
+        add(1002, 5, Transfer { to: 40, amount: 50 });
+        add(
+            1003,
+            6,
+            Transfer {
+                to: 1,
+                amount: 100_000,
+            },
+        );
+        add(1004, 3, Transfer { to: 3, amount: 1 });
+        add(1000, 1, Transfer { to: 2, amount: 1 });
+        add(1005, 8, Transfer { to: 9, amount: 10 });
+        add(1000, 3, Dispute);
+        add(1000, 2, Chargeback);
+        add(1001, 4, Dispute);
+        add(1001, 6, Resolve);
+        add(1002, 40, Dispute);
+        add(1002, 40, Chargeback);
+        add(1002, 5, Deposit(1));
+        add(1006, 9, Transfer { to: 8, amount: 10 });
+        add(1006, 8, Dispute);
+
+        // ids colliding across clients
+        add(0, 7, Deposit(100));
+        add(3, 8, Deposit(100));
+        add(3, 9, Deposit(100));
+        add(999, 30, Deposit(100));
+        add(999, 31, Deposit(100));
+
+        transactions
+    }
+
+    #[test]
+    fn produces_same_result_as_single_engine() {
+        let mut engine = PaymentEngine::default();
+        let mut expected_failures: Vec<(usize, ExecutionError)> = Vec::new();
+        for (index, transaction) in transactions().into_iter().enumerate() {
+            if let Err(e) = engine.execute(transaction) {
+                expected_failures.push((index, e));
+            }
+        }
+        let expected_clients = snapshot(engine.into_clients().unwrap());
+
+        for shards in [1, 2, 3, 8] {
+            let mut sharded = ShardedPaymentEngine::new(shards);
+            for (index, transaction) in transactions().into_iter().enumerate() {
+                sharded.execute(transaction, index);
+            }
+            let result = sharded.finish();
+
+            assert_eq!(snapshot(&result.clients), expected_clients);
+            assert_eq!(result.failures, expected_failures);
+        }
+    }
+}
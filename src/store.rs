@@ -0,0 +1,549 @@
+use crate::client::{Client, ClientId};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// the storage of all clients of a [PaymentEngine]
+///
+/// the engine touches at most two clients per transaction (the client and the counterparty of a
+/// transfer). Once both have been staged, it loads them together with [ClientStore::get_pair]
+/// before the transaction is journaled, so that committing to them requires no further i/o.
+///
+/// [PaymentEngine]: crate::PaymentEngine
+pub trait ClientStore {
+    /// loads the client with the given id
+    ///
+    /// # Errors
+    /// if the client cannot be loaded or another one cannot be written to make room for it
+    fn get(&mut self, id: ClientId) -> io::Result<Option<&mut Client>>;
+
+    /// loads a client together with another one, which is only loaded if it differs
+    ///
+    /// # Errors
+    /// if either client cannot be loaded or others cannot be written to make room for them
+    fn get_pair(
+        &mut self,
+        id: ClientId,
+        other: Option<ClientId>,
+    ) -> io::Result<(Option<&mut Client>, Option<&mut Client>)>;
+
+    /// adds a client that has not been stored before
+    ///
+    /// adding a client never fails, a store that has to write other clients to make room for it
+    /// does so on the next load.
+    fn put(&mut self, client: Client);
+
+    /// calls `f` for every stored client ordered by id
+    fn for_each<F: FnMut(&Client)>(&mut self, f: F) -> io::Result<()>;
+
+    /// reads the client with the given id without loading it into memory
+    ///
+    /// # Errors
+    /// if the client cannot be read
+    fn client(&self, id: ClientId) -> io::Result<Option<Cow<'_, Client>>>;
+
+    /// reads all clients in arbitrary order without loading them into memory
+    fn clients(&self) -> impl Iterator<Item = io::Result<Cow<'_, Client>>>;
+
+    /// consumes the store into all clients ordered by id
+    ///
+    /// # Errors
+    /// if a client cannot be read
+    fn into_clients(self) -> io::Result<Vec<Client>>;
+}
+
+/// a [ClientStore] that keeps all clients in memory
+#[derive(Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MemoryStore(HashMap<ClientId, Client>);
+
+impl MemoryStore {
+    pub(crate) fn client_mut(&mut self, id: ClientId) -> Option<&mut Client> {
+        self.0.get_mut(&id)
+    }
+
+    pub(crate) fn insert(&mut self, client: Client) {
+        self.0.insert(client.id(), client);
+    }
+
+    pub(crate) fn remove(&mut self, id: ClientId) -> Option<Client> {
+        self.0.remove(&id)
+    }
+}
+
+impl ClientStore for MemoryStore {
+    fn get(&mut self, id: ClientId) -> io::Result<Option<&mut Client>> {
+        Ok(self.0.get_mut(&id))
+    }
+
+    fn get_pair(
+        &mut self,
+        id: ClientId,
+        other: Option<ClientId>,
+    ) -> io::Result<(Option<&mut Client>, Option<&mut Client>)> {
+        Ok(match other.filter(|other| *other != id) {
+            Some(other) => {
+                let [client, other] = self.0.get_disjoint_mut([&id, &other]);
+                (client, other)
+            }
+            None => (self.0.get_mut(&id), None),
+        })
+    }
+
+    fn put(&mut self, client: Client) {
+        self.insert(client);
+    }
+
+    fn for_each<F: FnMut(&Client)>(&mut self, f: F) -> io::Result<()> {
+        let mut clients: Vec<&Client> = self.0.values().collect();
+        clients.sort_unstable_by_key(|client| client.id());
+        clients.into_iter().for_each(f);
+
+        Ok(())
+    }
+
+    fn client(&self, id: ClientId) -> io::Result<Option<Cow<'_, Client>>> {
+        Ok(self.0.get(&id).map(Cow::Borrowed))
+    }
+
+    fn clients(&self) -> impl Iterator<Item = io::Result<Cow<'_, Client>>> {
+        self.0.values().map(|client| Ok(Cow::Borrowed(client)))
+    }
+
+    fn into_clients(self) -> io::Result<Vec<Client>> {
+        let mut clients: Vec<Client> = self.0.into_values().collect();
+        clients.sort_unstable_by_key(Client::id);
+
+        Ok(clients)
+    }
+}
+
+/// the location of a client within the file of a [DiskStore]
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    offset: u64,
+    length: u64,
+}
+
+struct Cached {
+    client: Client,
+    last_used: u64,
+}
+
+/// a [ClientStore] that only keeps the most recently used clients in memory
+///
+/// all other clients (including their booking history) are appended as JSON lines to a file,
+/// an in-memory index points to the latest copy of every client. Every eviction of a client
+/// appends a new copy of it, the file is compacted once most of it consists of outdated copies.
+/// It is not meant to be reopened.
+///
+/// clients are loaded and written as a whole, so the store only bounds the memory of clients
+/// that are not in use. The transaction registry of the engine is not part of the store and
+/// stays in memory.
+pub struct DiskStore {
+    path: PathBuf,
+    file: File,
+    /// the length of the file, new copies are appended here
+    end: u64,
+    /// the length of all outdated copies within the file
+    garbage: u64,
+    /// the least amount of garbage that is worth a compaction
+    compaction_threshold: u64,
+    index: HashMap<ClientId, Location>,
+    cache: HashMap<ClientId, Cached>,
+    /// the cached clients ordered by their last use, the least recently used one first
+    recency: BTreeMap<u64, ClientId>,
+    capacity: usize,
+    /// a counter ordering the uses of cached clients
+    clock: u64,
+}
+
+impl DiskStore {
+    /// smallest number of cached clients, the client and counterparty of a transfer
+    pub const MIN_CAPACITY: usize = 2;
+
+    /// the default least amount of garbage that is worth a compaction
+    const COMPACTION_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+    /// creates a store backed by the given file, which is truncated
+    ///
+    /// at most `capacity` clients (at least [DiskStore::MIN_CAPACITY]) are kept in memory.
+    pub fn create(path: &Path, capacity: usize) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Self::open(path)?,
+            end: 0,
+            garbage: 0,
+            compaction_threshold: Self::COMPACTION_THRESHOLD,
+            index: HashMap::default(),
+            cache: HashMap::default(),
+            recency: BTreeMap::default(),
+            capacity: capacity.max(Self::MIN_CAPACITY),
+            clock: 0,
+        })
+    }
+
+    fn open(path: &Path) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+    }
+
+    /// reads the copy of a client at the given location of the file
+    fn read(mut file: &File, location: Location) -> io::Result<Client> {
+        let mut line = vec![0; location.length as usize];
+        file.seek(SeekFrom::Start(location.offset))?;
+        file.read_exact(&mut line)?;
+
+        Ok(serde_json::from_slice(&line)?)
+    }
+
+    fn write(&mut self, client: &Client) -> io::Result<Location> {
+        let mut line = serde_json::to_vec(client)?;
+        line.push(b'\n');
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(&line)?;
+
+        let location = Location {
+            offset: self.end,
+            length: line.len() as u64,
+        };
+        self.end += location.length;
+
+        Ok(location)
+    }
+
+    /// writes the least recently used clients to the file until another one fits into the cache
+    ///
+    /// a client that cannot be written stays cached, so that nothing is lost.
+    fn make_room(&mut self) -> io::Result<()> {
+        while self.cache.len() >= self.capacity {
+            let Some((last_used, id)) = self.recency.pop_first() else {
+                break;
+            };
+
+            let cached = self
+                .cache
+                .remove(&id)
+                .expect("every client in the recency order is cached");
+            match self.write(&cached.client) {
+                Ok(location) => {
+                    self.index.insert(id, location);
+                }
+                Err(e) => {
+                    self.recency.insert(last_used, id);
+                    self.cache.insert(id, cached);
+                    return Err(e);
+                }
+            }
+        }
+
+        if self.garbage >= self.compaction_threshold && self.garbage * 2 > self.end {
+            // the current file stays valid if the compaction fails, so it is merely retried
+            if let Err(e) = self.compact() {
+                warn!("could not compact {}: {}", self.path.display(), e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// rewrites the file with only the latest copy of every client that is not cached
+    ///
+    /// the copies are written to a temporary file first, which then replaces the current one.
+    fn compact(&mut self) -> io::Result<()> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".compact");
+        let temporary = PathBuf::from(temporary);
+
+        let mut file = Self::open(&temporary)?;
+        let mut locations: Vec<(ClientId, Location)> = self
+            .index
+            .iter()
+            .map(|(id, location)| (*id, *location))
+            .collect();
+        locations.sort_unstable_by_key(|(_, location)| location.offset);
+
+        let mut index = HashMap::with_capacity(locations.len());
+        let mut end = 0;
+        for (id, location) in locations {
+            let mut line = vec![0; location.length as usize];
+            self.file.seek(SeekFrom::Start(location.offset))?;
+            self.file.read_exact(&mut line)?;
+            file.write_all(&line)?;
+
+            index.insert(
+                id,
+                Location {
+                    offset: end,
+                    length: location.length,
+                },
+            );
+            end += location.length;
+        }
+        fs::rename(&temporary, &self.path)?;
+
+        self.file = file;
+        self.index = index;
+        self.end = end;
+        self.garbage = 0;
+
+        Ok(())
+    }
+}
+
+impl ClientStore for DiskStore {
+    fn get(&mut self, id: ClientId) -> io::Result<Option<&mut Client>> {
+        match self.cache.get_mut(&id) {
+            Some(cached) => {
+                self.recency.remove(&cached.last_used);
+            }
+            None => {
+                self.make_room()?;
+
+                let Some(location) = self.index.get(&id).copied() else {
+                    return Ok(None);
+                };
+                let client = Self::read(&self.file, location)?;
+                // the cached client supersedes its copy in the file
+                self.index.remove(&id);
+                self.garbage += location.length;
+                self.cache.insert(
+                    id,
+                    Cached {
+                        client,
+                        last_used: 0,
+                    },
+                );
+            }
+        }
+
+        self.clock += 1;
+        self.recency.insert(self.clock, id);
+        Ok(self.cache.get_mut(&id).map(|cached| {
+            cached.last_used = self.clock;
+            &mut cached.client
+        }))
+    }
+
+    /// loading the second client keeps the first one cached, as it has just been used
+    fn get_pair(
+        &mut self,
+        id: ClientId,
+        other: Option<ClientId>,
+    ) -> io::Result<(Option<&mut Client>, Option<&mut Client>)> {
+        let other = other.filter(|other| *other != id);
+        for id in [Some(id), other].into_iter().flatten() {
+            self.get(id)?;
+        }
+
+        let [client, other] = match other {
+            Some(other) => self.cache.get_disjoint_mut([&id, &other]),
+            None => [self.cache.get_mut(&id), None],
+        };
+
+        Ok((
+            client.map(|cached| &mut cached.client),
+            other.map(|cached| &mut cached.client),
+        ))
+    }
+
+    /// adds the client to the cache, room is made by the next [DiskStore::get]
+    fn put(&mut self, client: Client) {
+        self.clock += 1;
+        self.index.remove(&client.id());
+        self.recency.insert(self.clock, client.id());
+        self.cache.insert(
+            client.id(),
+            Cached {
+                client,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// cached clients are visited as they are, all others are read one at a time
+    fn for_each<F: FnMut(&Client)>(&mut self, mut f: F) -> io::Result<()> {
+        let mut ids: Vec<ClientId> = self
+            .cache
+            .keys()
+            .chain(self.index.keys())
+            .copied()
+            .collect();
+        ids.sort_unstable();
+
+        for id in ids {
+            match self.index.get(&id).copied() {
+                Some(location) => f(&Self::read(&self.file, location)?),
+                None => f(&self.cache[&id].client),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// a client that is not cached is read through a file handle of its own, so that reading
+    /// does not interfere with the handle the store writes through
+    fn client(&self, id: ClientId) -> io::Result<Option<Cow<'_, Client>>> {
+        if let Some(cached) = self.cache.get(&id) {
+            return Ok(Some(Cow::Borrowed(&cached.client)));
+        }
+
+        match self.index.get(&id) {
+            Some(location) => Ok(Some(Cow::Owned(Self::read(
+                &File::open(&self.path)?,
+                *location,
+            )?))),
+            None => Ok(None),
+        }
+    }
+
+    fn clients(&self) -> impl Iterator<Item = io::Result<Cow<'_, Client>>> {
+        let file = File::open(&self.path);
+        let cached = self
+            .cache
+            .values()
+            .map(|cached| Ok(Cow::Borrowed(&cached.client)));
+        let stored = self.index.values().map(move |location| {
+            let file = file
+                .as_ref()
+                .map_err(|e| io::Error::new(e.kind(), e.to_string()))?;
+            Ok(Cow::Owned(Self::read(file, *location)?))
+        });
+
+        cached.chain(stored)
+    }
+
+    fn into_clients(self) -> io::Result<Vec<Client>> {
+        let mut clients = self
+            .index
+            .values()
+            .map(|location| Self::read(&self.file, *location))
+            .collect::<io::Result<Vec<Client>>>()?;
+        clients.extend(self.cache.into_values().map(|cached| cached.client));
+        clients.sort_unstable_by_key(Client::id);
+
+        Ok(clients)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{Client, LockReason};
+    use crate::store::{ClientStore, DiskStore, MemoryStore};
+    use std::env::temp_dir;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn path(name: &str) -> PathBuf {
+        temp_dir().join(format!(
+            "payment_engine_{}_{}.jsonl",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn ids<S: ClientStore>(store: &mut S) -> Vec<u16> {
+        let mut ids = Vec::new();
+        store.for_each(|client| ids.push(client.id())).unwrap();
+        ids
+    }
+
+    #[test]
+    fn memory_store_iterates_by_id() {
+        let mut store = MemoryStore::default();
+        for id in [3, 1, 2] {
+            store.put(Client::new(id));
+        }
+
+        assert!(store.get(2).unwrap().is_some());
+        assert!(store.get(4).unwrap().is_none());
+        assert_eq!(ids(&mut store), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn disk_store_evicts_least_recently_used() {
+        let path = path("store");
+        let mut store = DiskStore::create(&path, 2).unwrap();
+        for id in [3, 1, 2] {
+            store.get(id).unwrap();
+            store.put(Client::new(id));
+        }
+
+        // client 3 has been written to make room for client 2
+        assert!(store.index.contains_key(&3));
+        assert_eq!(ids(&mut store), vec![1, 2, 3]);
+
+        // changes to an evicted client are written along with it
+        store.get(3).unwrap().unwrap().lock(LockReason::Manual);
+        for id in [4, 1, 2] {
+            store.get(id).unwrap();
+        }
+        assert!(store.index.contains_key(&3));
+        assert!(store.get(3).unwrap().unwrap().is_locked());
+        assert_eq!(ids(&mut store), vec![1, 2, 3]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn loads_pairs() {
+        let path = path("pair");
+        let mut disk = DiskStore::create(&path, 2).unwrap();
+        let mut memory = MemoryStore::default();
+        for id in [1, 2, 3] {
+            disk.get(id).unwrap();
+            disk.put(Client::new(id));
+            memory.put(Client::new(id));
+        }
+
+        fn loaded<S: ClientStore>(
+            store: &mut S,
+            id: u16,
+            other: Option<u16>,
+        ) -> (Option<u16>, Option<u16>) {
+            let (client, other) = store.get_pair(id, other).unwrap();
+            (client.map(|c| c.id()), other.map(|c| c.id()))
+        }
+
+        assert_eq!(loaded(&mut disk, 1, Some(3)), (Some(1), Some(3)));
+        assert_eq!(loaded(&mut disk, 2, Some(4)), (Some(2), None));
+        assert_eq!(loaded(&mut disk, 1, Some(1)), (Some(1), None));
+        assert_eq!(loaded(&mut memory, 3, Some(2)), (Some(3), Some(2)));
+        assert_eq!(loaded(&mut memory, 3, None), (Some(3), None));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn disk_store_compacts_outdated_copies() {
+        let path = path("compact");
+        let mut store = DiskStore::create(&path, 2).unwrap();
+        store.compaction_threshold = 0;
+
+        // every round writes a new copy of every client, which outdates the previous one
+        for _ in 0..10 {
+            for id in [1, 2, 3, 4] {
+                if store.get(id).unwrap().is_none() {
+                    store.put(Client::new(id));
+                }
+            }
+        }
+        store.get(4).unwrap().unwrap().lock(LockReason::Manual);
+
+        // without compaction the file would hold more than 30 copies
+        let copy = serde_json::to_vec(&Client::new(1)).unwrap().len() as u64 + 1;
+        assert!(store.end <= 6 * copy);
+        assert_eq!(fs::metadata(&path).unwrap().len(), store.end);
+        assert_eq!(ids(&mut store), vec![1, 2, 3, 4]);
+        assert!(store.get(4).unwrap().unwrap().is_locked());
+        fs::remove_file(path).unwrap();
+    }
+}
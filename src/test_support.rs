@@ -0,0 +1,34 @@
+use crate::asset::Asset;
+use crate::client::{Client, ClientId, TransactionId};
+use std::borrow::Borrow;
+
+/// the balances, status and booking states of every client ordered by id
+pub type Snapshot = Vec<(
+    ClientId,
+    Vec<(Asset, u64, u64)>,
+    String,
+    Vec<(TransactionId, String)>,
+)>;
+
+/// the observable state of the given clients, so that engines can be compared regardless of the
+/// order they keep their clients in
+pub fn snapshot(clients: impl IntoIterator<Item = impl Borrow<Client>>) -> Snapshot {
+    let mut snapshot: Snapshot = clients
+        .into_iter()
+        .map(|c| {
+            let c = c.borrow();
+            let mut bookings: Vec<_> = c
+                .bookings()
+                .map(|b| (b.tx(), format!("{:?}", b.state())))
+                .collect();
+            bookings.sort();
+            let balances = c
+                .balances()
+                .map(|(asset, b)| (*asset, b.available(), b.frozen()))
+                .collect();
+            (c.id(), balances, format!("{:?}", c.status()), bookings)
+        })
+        .collect();
+    snapshot.sort();
+    snapshot
+}
@@ -0,0 +1,75 @@
+//! compares the reference model of the input generator with the engine binary
+//!
+//! the expected output written by `generate --expected` has to match the output of the binary
+//! with its default options byte by byte, just like the README suggests:
+//! ```
+//! cargo run --release -- input.csv | diff - expected.csv
+//! ```
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn model_matches_the_binary() {
+    let directory = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let input = directory.join("generated_input.csv");
+    let expected = directory.join("generated_expected.csv");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_generate"))
+        .args([
+            "--rows",
+            "20000",
+            "--clients",
+            "50",
+            "--disputes",
+            "40",
+            "--chargebacks",
+            "0.3",
+            "--invalid",
+            "0.05",
+            "--locked",
+            "0.2",
+            "--whitespace",
+            "0.5",
+        ])
+        .arg("--output")
+        .arg(&input)
+        .arg("--expected")
+        .arg(&expected)
+        .status()
+        .expect("could not run generator");
+    assert!(status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_booking_engine"))
+        .arg(&input)
+        .output()
+        .expect("could not run payment engine");
+    assert!(output.status.success());
+
+    let expected = fs::read_to_string(expected).unwrap();
+    assert!(expected.lines().any(|row| row.ends_with(",true")));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn accepts_weights_beyond_u32() {
+    let output = Command::new(env!("CARGO_BIN_EXE_generate"))
+        .args([
+            "--rows",
+            "100",
+            "--deposits",
+            "4000000000",
+            "--withdrawals",
+            "400000000",
+            "--disputes",
+            "4000000000",
+        ])
+        .output()
+        .expect("could not run generator");
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().lines().count(),
+        101
+    );
+}
@@ -0,0 +1,378 @@
+//! end-to-end tests of the binary against golden files
+//!
+//! every directory in `tests/cases` holds an `input.csv` and the `expected.csv` the binary writes
+//! for it. Rows rejected by the engine are compared against `rejects.csv`, a missing file
+//! expects no rejections. Additional command line arguments can be given in an `args` file, one
+//! argument per line, lines starting with `#` are comments. The provided `input.csv` and
+//! `output.csv` in the root of the repository are checked as well. The binary has to exit
+//! successfully for every case.
+//!
+//! The rejects of `tests/cases/dispute_window_age_expired` are fed back into the binary to check
+//! that a report keeps every field needed to retry its rows.
+//!
+//! cases in `tests/cases_linux` write to `/dev/full` to provoke i/o errors and only run on linux.
+//!
+//! rows are compared in order, as the output is sorted deterministically. Only cases that run
+//! sharded (`--threads`) or on additional inputs are compared regardless of the row order.
+//! Amounts are compared regardless of their decimal places.
+//! After an intended change of the output, the golden files are rewritten with
+//! ```
+//! UPDATE_GOLDEN=1 cargo test --test golden
+//! ```
+use booking_engine::Decimal;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// a directory with an input and its golden files
+struct Case {
+    name: String,
+    directory: PathBuf,
+    input: PathBuf,
+    expected: PathBuf,
+}
+
+impl Case {
+    fn new(directory: PathBuf) -> Self {
+        Self {
+            name: directory.file_name().unwrap().to_string_lossy().to_string(),
+            input: directory.join("input.csv"),
+            expected: directory.join("expected.csv"),
+            directory,
+        }
+    }
+
+    /// the example provided in the root of the repository
+    fn provided() -> Self {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        Self {
+            name: "provided".to_string(),
+            directory: root.to_path_buf(),
+            input: root.join("input.csv"),
+            expected: root.join("output.csv"),
+        }
+    }
+
+    fn rejects(&self) -> PathBuf {
+        self.directory.join("rejects.csv")
+    }
+
+    /// whether the rows are compared in order, which is only given for a single, unsharded input
+    fn is_ordered(&self) -> bool {
+        // options that are not followed by a value, every other option takes one
+        const FLAGS: [&str; 2] = ["--status", "--fixed-decimals"];
+
+        let mut expects_value = false;
+        for arg in self.args() {
+            if arg == "--threads" {
+                return false;
+            }
+            match arg.starts_with("--") {
+                true => expects_value = !FLAGS.contains(&arg.as_str()) && !arg.contains('='),
+                // an argument that is not the value of an option is another input
+                false if !expects_value => return false,
+                false => expects_value = false,
+            }
+        }
+
+        true
+    }
+
+    fn args(&self) -> Vec<String> {
+        fs::read_to_string(self.directory.join("args"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// runs the binary on the input and returns its output and rejected rows
+    ///
+    /// # Errors
+    /// if the binary does not exit successfully, with a description of its exit status
+    fn run(&self) -> Result<(String, String), String> {
+        let rejects =
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.rejects.csv", self.name));
+        let _ = fs::remove_file(&rejects);
+
+        let output = Command::new(env!("CARGO_BIN_EXE_booking_engine"))
+            .arg(&self.input)
+            .arg("--rejects")
+            .arg(&rejects)
+            .args(self.args())
+            .current_dir(&self.directory)
+            .output()
+            .expect("could not run payment engine");
+
+        match output.status.success() {
+            true => Ok((
+                String::from_utf8(output.stdout).unwrap(),
+                fs::read_to_string(&rejects).unwrap_or_default(),
+            )),
+            false => Err(self.failure(&output)),
+        }
+    }
+
+    fn failure(&self, output: &Output) -> String {
+        format!(
+            "{}: {}\n--- stderr\n{}",
+            self.name,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        )
+    }
+
+    /// compares the output with the golden files, returns a description of every difference
+    fn check(&self) -> Vec<String> {
+        let (output, rejects) = match self.run() {
+            Ok(result) => result,
+            Err(failure) => return vec![failure],
+        };
+        let expected_rejects = fs::read_to_string(self.rejects()).unwrap_or_default();
+
+        [
+            ("output", &self.expected, output, read(&self.expected)),
+            ("rejects", &self.rejects(), rejects, expected_rejects),
+        ]
+        .into_iter()
+        .filter(|(_, _, actual, expected)| {
+            normalize(actual, self.is_ordered()) != normalize(expected, self.is_ordered())
+        })
+        .map(|(kind, path, actual, expected)| {
+            format!(
+                "{}: {} differs from {}\n--- expected\n{}--- actual\n{}",
+                self.name,
+                kind,
+                path.display(),
+                expected,
+                actual
+            )
+        })
+        .collect()
+    }
+
+    /// rewrites the golden files with the current output
+    fn update(&self) {
+        let (output, rejects) = self.run().unwrap_or_else(|failure| panic!("{}", failure));
+        fs::write(&self.expected, output).unwrap();
+
+        match rejects.is_empty() {
+            true => {
+                let _ = fs::remove_file(self.rejects());
+            }
+            false => fs::write(self.rejects(), rejects).unwrap(),
+        }
+    }
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| panic!("could not read {}: {}", path.display(), e))
+}
+
+/// a field of a csv row, amounts are compared by their value
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Field {
+    Amount(u64),
+    Text(String),
+}
+
+/// the header and the rows of a csv, sorted unless their order is compared
+fn normalize(csv: &str, is_ordered: bool) -> (Vec<String>, Vec<Vec<Field>>) {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(csv.as_bytes());
+    let mut records = reader.records().map(|record| record.unwrap());
+    let header = records
+        .next()
+        .map(|header| header.iter().map(str::to_string).collect())
+        .unwrap_or_default();
+
+    let mut rows: Vec<Vec<Field>> = records
+        .map(|record| {
+            record
+                .iter()
+                .map(|field| match field.trim().parse::<Decimal>() {
+                    Ok(amount) => Field::Amount(amount.minor_units()),
+                    Err(_) => Field::Text(field.trim().to_string()),
+                })
+                .collect()
+        })
+        .collect();
+    if !is_ordered {
+        rows.sort();
+    }
+
+    (header, rows)
+}
+
+/// all cases in the given directory ordered by name
+fn cases(directory: &str) -> Vec<Case> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join(directory);
+    let mut cases: Vec<Case> = fs::read_dir(directory)
+        .expect("could not read test cases")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .map(Case::new)
+        .collect();
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+
+    cases
+}
+
+/// checks all cases, or rewrites their golden files if requested
+fn check_all(cases: Vec<Case>) {
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        cases.iter().for_each(Case::update);
+        return;
+    }
+
+    let failures: Vec<String> = cases.iter().flat_map(Case::check).collect();
+    assert!(
+        failures.is_empty(),
+        "{} of {} cases failed\n{}",
+        failures.len(),
+        cases.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn matches_golden_files() {
+    let mut cases = cases("tests/cases");
+    cases.push(Case::provided());
+
+    check_all(cases);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn matches_golden_files_of_io_errors() {
+    check_all(cases("tests/cases_linux"));
+}
+
+/// the fields of the rejected rows, without the columns that describe the rejection
+fn rejected_fields(rejects: &str) -> (Vec<String>, Vec<Vec<Field>>) {
+    let (header, rows) = normalize(rejects, false);
+    let is_original = |name: &String| name != "line" && name != "reason";
+    let rows = rows
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .zip(&header)
+                .filter(|(_, name)| is_original(name))
+                .map(|(field, _)| field)
+                .collect()
+        })
+        .collect();
+
+    (header.into_iter().filter(is_original).collect(), rows)
+}
+
+#[test]
+fn rejected_rows_round_trip() {
+    let case = Case::new(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cases/dispute_window_age_expired"),
+    );
+    let rejects = read(&case.rejects());
+    let retry = Case {
+        name: format!("{}_retry", case.name),
+        input: case.rejects(),
+        ..case
+    };
+    let (_, retried_rejects) = retry.run().unwrap_or_else(|failure| panic!("{}", failure));
+
+    assert!(rejects.lines().next().unwrap().contains("timestamp"));
+    assert_eq!(rejected_fields(&retried_rejects), rejected_fields(&rejects));
+}
+
+#[test]
+fn reports_a_missing_input_as_usage_error() {
+    let output = Command::new(env!("CARGO_BIN_EXE_booking_engine"))
+        .arg(Path::new(env!("CARGO_TARGET_TMPDIR")).join("missing.csv"))
+        .output()
+        .expect("could not run payment engine");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr.starts_with("error: could not open input "),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("Usage:"), "{}", stderr);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn reports_an_unwritable_rejects_file_as_error() {
+    let case = Case::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cases/invalid_state"));
+    let output = Command::new(env!("CARGO_BIN_EXE_booking_engine"))
+        .arg(&case.input)
+        .args(["--rejects", "/dev/full"])
+        .output()
+        .expect("could not run payment engine");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr.starts_with("error: could not write rejects /dev/full: "),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn reports_an_unwritable_output_as_error() {
+    let output = Command::new(env!("CARGO_BIN_EXE_booking_engine"))
+        .arg(Case::provided().input)
+        .args(["--output", "/dev/full"])
+        .output()
+        .expect("could not run payment engine");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr.starts_with("error: could not write output /dev/full: "),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn compares_amounts_by_value() {
+    assert_eq!(
+        normalize("client,available\n2,1.5\n1,0.0001\n", false),
+        normalize("client,available\n1,0.0001\n2,1.5000\n", false)
+    );
+    assert_ne!(
+        normalize("client,available\n2,1.5\n1,0.0001\n", true),
+        normalize("client,available\n1,0.0001\n2,1.5000\n", true)
+    );
+    assert_ne!(
+        normalize("client,available\n1,1.5\n", true),
+        normalize("client,total\n1,1.5\n", true)
+    );
+    assert_ne!(
+        normalize("client,available\n1,1.5\n", true),
+        normalize("client,available\n1,1.05\n", true)
+    );
+}
+
+#[test]
+fn compares_sharded_and_multiple_inputs_regardless_of_order() {
+    let case = |args: &[&str]| {
+        let directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ordered_case");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("args"), args.join("\n")).unwrap();
+        Case::new(directory)
+    };
+
+    assert!(case(&["--status", "--dispute-window", "1"]).is_ordered());
+    assert!(!case(&["--threads", "2"]).is_ordered());
+    assert!(!case(&["--status", "other.csv"]).is_ordered());
+    assert!(!case(&["--journal", "/dev/full", "-"]).is_ordered());
+}